@@ -0,0 +1,142 @@
+//! Derivation of the addresses of the keys, shared so that both tools use the same ones.
+
+use anyhow::{ensure, Context, Result};
+use iota_sdk::{
+    client::{api::GetAddressesOptions, secret::SecretManager, Client},
+    types::block::address::{Bech32Address, Hrp},
+};
+
+use crate::keys::KeyFormat;
+
+/// Returns the options the addresses are derived with, the coin type and HRP come from the node.
+async fn options(client: &Client) -> Result<GetAddressesOptions> {
+    Ok(GetAddressesOptions::from_client(client)
+        .await?
+        .with_account_index(0)
+        .with_range(0..1))
+}
+
+/// Derives the first address of the key with the given index in the list of keys.
+async fn derive(
+    key_index: usize,
+    secret_manager: &SecretManager,
+    options: GetAddressesOptions,
+) -> Result<Bech32Address> {
+    let range = options.range.clone();
+    let mut addresses = secret_manager.generate_ed25519_addresses(options).await?;
+    addresses
+        .pop()
+        .with_context(|| format!("no address derived for key {key_index} in the range {range:?}"))
+}
+
+/// Derives the first address of the key with the given index, the only one that is used.
+pub async fn first_address(
+    client: &Client,
+    key_index: usize,
+    secret_manager: &SecretManager,
+) -> Result<Bech32Address> {
+    derive(key_index, secret_manager, options(client).await?).await
+}
+
+/// Prints the address of each key as `<key index> <coin type>/<account index>/<address index>
+/// <address>`, one per line, so that the output of both tools can be compared directly.
+pub async fn print_addresses(
+    clients: &[Client],
+    keys: &[String],
+    key_format: KeyFormat,
+) -> Result<()> {
+    for (i, key) in keys.iter().enumerate() {
+        let client = &clients[i % clients.len()];
+        let secret_manager = key_format.secret_manager(key)?;
+        let options = options(client).await?;
+        let address = first_address(client, i, &secret_manager).await?;
+        println!(
+            "{i} {}/{}/{} {address}",
+            options.coin_type, options.account_index, options.range.start
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the options to derive addresses with the HRP without contacting a node.
+fn offline_options(hrp: Hrp) -> GetAddressesOptions {
+    GetAddressesOptions::default()
+        .with_bech32_hrp(hrp)
+        .with_account_index(0)
+        .with_range(0..1)
+}
+
+/// Prints the address of each key with the HRP, one per line, without contacting a node.
+pub async fn print_derived(keys: &[String], key_format: KeyFormat, hrp: Hrp) -> Result<()> {
+    let options = offline_options(hrp);
+    for (i, key) in keys.iter().enumerate() {
+        let secret_manager = key_format.secret_manager(key)?;
+        println!("{}", derive(i, &secret_manager, options.clone()).await?);
+    }
+
+    Ok(())
+}
+
+/// Parses each key and derives its address with the HRP, without contacting a node, reporting
+/// every invalid key and failing if there is any.
+pub async fn validate(keys: &[String], key_format: KeyFormat, hrp: Hrp) -> Result<()> {
+    let options = offline_options(hrp);
+    let mut valid = 0;
+    for (i, key) in keys.iter().enumerate() {
+        let address = match key_format.secret_manager(key) {
+            Ok(secret_manager) => derive(i, &secret_manager, options.clone()).await,
+            Err(err) => Err(err),
+        };
+        match address {
+            Ok(_) => valid += 1,
+            Err(err) => eprintln!("Key {i} is invalid: {err:#}"),
+        }
+    }
+    println!("{valid} of {} keys are valid", keys.len());
+    ensure!(
+        valid == keys.len(),
+        "{} keys are invalid",
+        keys.len() - valid
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hrp() -> Hrp {
+        Hrp::from_str_unchecked("rms")
+    }
+
+    #[tokio::test]
+    async fn derive_fails_cleanly_for_an_empty_range() {
+        // unlike a private key, a seed derives one address per index of the range
+        let secret_manager =
+            SecretManager::try_from_hex_seed(format!("0x{}", "00".repeat(64))).unwrap();
+        let options = offline_options(hrp()).with_range(0..0);
+
+        let err = derive(3, &secret_manager, options).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no address derived for key 3 in the range 0..0"
+        );
+    }
+
+    #[tokio::test]
+    async fn derive_returns_the_address_of_the_key() {
+        let secret_manager = KeyFormat::Base58
+            .secret_manager("11111111111111111111111111111111")
+            .unwrap();
+
+        let address = derive(0, &secret_manager, offline_options(hrp()))
+            .await
+            .unwrap();
+        assert_eq!(
+            address.to_string(),
+            "rms1qp5fmt30w7cy3hxq3c2dwvgyag2zy26mu9xvx8e5594pyg0egnq7x2yvqdu"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Parsing of human friendly amounts like `1.5IOTA` or `500kglow`.
+
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context, Error, Result};
+use iota_sdk::types::api::core::response::BaseTokenResponse;
+
+/// Unit of an [`Amount`] given on the command line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Unit {
+    Glow,
+    KiloGlow,
+    /// Whole tokens, with the unit name of the network token.
+    Token(String),
+}
+
+/// Amount given on the command line, resolved to glow once the network decimals are known.
+///
+/// A plain integer is interpreted as glow. Decimal numbers require an explicit unit to not be
+/// ambiguous.
+#[derive(Clone, Debug)]
+pub struct Amount {
+    input: String,
+    integer: String,
+    fraction: String,
+    unit: Unit,
+}
+
+impl FromStr for Amount {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let input = s.trim();
+        let split = input
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(input.len());
+        let (number, unit) = input.split_at(split);
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        ensure!(
+            (!integer.is_empty() || !fraction.is_empty()) && !fraction.contains('.'),
+            "invalid number in amount '{input}'"
+        );
+
+        let unit = match unit.trim().to_lowercase().as_str() {
+            "" if !fraction.is_empty() => {
+                bail!("ambiguous amount '{input}', add a unit like '{input}IOTA' or '{input}kglow'")
+            }
+            "" | "glow" => Unit::Glow,
+            "kglow" => Unit::KiloGlow,
+            unit if unit.chars().all(|c| c.is_ascii_alphabetic()) => Unit::Token(unit.to_string()),
+            unit => bail!("invalid unit '{unit}' in amount '{input}'"),
+        };
+
+        Ok(Self {
+            input: input.to_string(),
+            integer: integer.to_string(),
+            fraction: fraction.to_string(),
+            unit,
+        })
+    }
+}
+
+impl Amount {
+    /// Converts the amount to glow using the decimals of the network's base token.
+    pub fn to_glow(&self, base_token: &BaseTokenResponse) -> Result<u64> {
+        let exponent = match &self.unit {
+            Unit::Glow => 0,
+            Unit::KiloGlow => 3,
+            Unit::Token(unit) => {
+                ensure!(
+                    unit.eq_ignore_ascii_case(&base_token.unit),
+                    "unit of amount '{}' does not match the network token {}",
+                    self.input,
+                    base_token.unit
+                );
+                base_token.decimals as usize
+            }
+        };
+        ensure!(
+            self.fraction.len() <= exponent,
+            "amount '{}' has more than {exponent} decimals",
+            self.input
+        );
+
+        let digits = format!("{}{:0<exponent$}", self.integer, self.fraction);
+        digits
+            .parse()
+            .with_context(|| format!("amount '{}' is too large", self.input))
+    }
+}
@@ -0,0 +1,50 @@
+//! Node URLs and keys stored in the OS keyring.
+
+use anyhow::{Context, Result};
+use dialoguer::{Input, Password};
+use serde::{Deserialize, Serialize};
+
+/// Service name of the keyring entries.
+const SERVICE: &str = "iota-playground";
+
+#[derive(Serialize, Deserialize)]
+pub struct Credentials {
+    pub node_url: Vec<String>,
+    pub keys: Vec<String>,
+}
+
+/// Loads the credentials of a keyring entry, prompting for them and storing them on first use.
+pub fn load_or_prompt(name: &str) -> Result<Credentials> {
+    let entry = keyring::Entry::new(SERVICE, name)?;
+    match entry.get_password() {
+        Ok(secret) => {
+            serde_json::from_str(&secret).with_context(|| format!("invalid keyring entry '{name}'"))
+        }
+        Err(keyring::Error::NoEntry) => {
+            eprintln!("Keyring entry '{name}' not found, creating it");
+            let node_url: String = Input::new()
+                .with_prompt("Node URLs, separated by commas")
+                .interact_text()?;
+            let keys = Password::new()
+                .with_prompt("Base58 encoded private keys, separated by commas")
+                .interact()?;
+
+            let credentials = Credentials {
+                node_url: split_list(&node_url),
+                keys: split_list(&keys),
+            };
+            entry.set_password(&serde_json::to_string(&credentials)?)?;
+
+            Ok(credentials)
+        }
+        Err(e) => Err(e).with_context(|| format!("failed to read keyring entry '{name}'")),
+    }
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
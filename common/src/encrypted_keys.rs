@@ -0,0 +1,47 @@
+//! Keys stored in a passphrase encrypted age file.
+
+use std::{io::Read, path::Path};
+
+use age::secrecy::Secret;
+use anyhow::{bail, Context, Result};
+use dialoguer::Password;
+
+/// Environment variable holding the passphrase, it is prompted for if not set.
+const PASSPHRASE_ENV: &str = "KEYS_PASSPHRASE";
+
+/// Decrypts the keys of an age file in memory, one per line or separated by commas, ignoring
+/// `#` comments.
+pub fn read(path: &Path) -> Result<Vec<String>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open '{}'", path.display()))?;
+    let decryptor = match age::Decryptor::new(std::io::BufReader::new(file))
+        .with_context(|| format!("invalid age file '{}'", path.display()))?
+    {
+        age::Decryptor::Passphrase(decryptor) => decryptor,
+        age::Decryptor::Recipients(_) => {
+            bail!("'{}' is not encrypted with a passphrase", path.display())
+        }
+    };
+
+    let passphrase = match std::env::var(PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) => Password::new()
+            .with_prompt(format!("Passphrase for '{}'", path.display()))
+            .interact()?,
+    };
+    let mut plaintext = String::new();
+    decryptor
+        .decrypt(&Secret::new(passphrase), None)
+        .with_context(|| format!("failed to decrypt '{}'", path.display()))?
+        .read_to_string(&mut plaintext)
+        .with_context(|| format!("failed to decrypt '{}'", path.display()))?;
+
+    Ok(plaintext
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(','))
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(String::from)
+        .collect())
+}
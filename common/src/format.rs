@@ -0,0 +1,192 @@
+//! Formatting of glow amounts for display.
+
+use clap::ValueEnum;
+
+use crate::network::Network;
+
+/// Unit amounts are displayed in.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DisplayUnit {
+    Glow,
+    /// 1000 glow
+    Kglow,
+    /// 1 000 000 glow, a whole IOTA or SMR
+    Iota,
+    /// 1 000 000 whole tokens
+    Miota,
+}
+
+impl DisplayUnit {
+    /// Power of ten of glow in one unit.
+    pub fn exponent(self) -> u32 {
+        match self {
+            Self::Glow => 0,
+            Self::Kglow => 3,
+            Self::Iota => 6,
+            Self::Miota => 12,
+        }
+    }
+
+    /// Symbol displayed after the amount, based on the symbol of the network's token.
+    pub fn symbol(self, network: Network) -> String {
+        match self {
+            Self::Glow => "glow".to_string(),
+            Self::Kglow => "kglow".to_string(),
+            Self::Iota => network.token_symbol().to_string(),
+            Self::Miota => format!("M{}", network.token_symbol()),
+        }
+    }
+}
+
+/// How amounts are rounded when displayed with fewer decimals than the unit has.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Rounding {
+    /// Round down
+    Floor,
+    /// Round up
+    Ceil,
+    /// Round to the nearest value, halfway cases up
+    Nearest,
+}
+
+/// Display format of amounts.
+///
+/// This only affects how amounts are printed, the JSON output keeps the raw glow amounts.
+#[derive(Clone, Copy, Debug)]
+pub struct AmountFormat {
+    pub network: Network,
+    pub unit: DisplayUnit,
+    pub decimals: u32,
+    pub rounding: Rounding,
+}
+
+impl AmountFormat {
+    /// Formats the glow amount in the display unit, e.g. `1.500000 IOTA`.
+    pub fn format(&self, glow: u64) -> String {
+        format!(
+            "{} {}",
+            self.digits(glow.into()),
+            self.unit.symbol(self.network)
+        )
+    }
+
+    /// Formats the difference between two glow amounts with an explicit sign, e.g. `+1.500000 IOTA`.
+    pub fn format_change(&self, before: u64, after: u64) -> String {
+        let sign = if after < before { '-' } else { '+' };
+        format!(
+            "{sign}{} {}",
+            self.digits(after.abs_diff(before).into()),
+            self.unit.symbol(self.network)
+        )
+    }
+
+    fn digits(&self, glow: u128) -> String {
+        let exponent = self.unit.exponent();
+        let shown = if self.decimals < exponent {
+            let divisor = 10u128.pow(exponent - self.decimals);
+            let (quotient, remainder) = (glow / divisor, glow % divisor);
+            let round_up = match self.rounding {
+                Rounding::Floor => false,
+                Rounding::Ceil => remainder > 0,
+                Rounding::Nearest => remainder * 2 >= divisor,
+            };
+            quotient + u128::from(round_up)
+        } else {
+            glow * 10u128.pow(self.decimals - exponent)
+        };
+
+        let scale = 10u128.pow(self.decimals);
+        match self.decimals as usize {
+            0 => shown.to_string(),
+            width => format!("{}.{:0width$}", shown / scale, shown % scale),
+        }
+    }
+}
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+const SCALES: [(u64, &str); 6] = [
+    (1_000_000_000_000_000_000, "quintillion"),
+    (1_000_000_000_000_000, "quadrillion"),
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+/// Spells out a number below one thousand.
+fn hundreds_in_words(n: u64) -> String {
+    let mut words = Vec::new();
+    if n >= 100 {
+        words.push(format!("{} hundred", ONES[(n / 100) as usize]));
+    }
+    match n % 100 {
+        0 => {}
+        rest @ 1..=19 => words.push(ONES[rest as usize].to_string()),
+        rest if rest % 10 == 0 => words.push(TENS[(rest / 10) as usize].to_string()),
+        rest => words.push(format!(
+            "{}-{}",
+            TENS[(rest / 10) as usize],
+            ONES[(rest % 10) as usize]
+        )),
+    }
+    words.join(" ")
+}
+
+/// Spells out a whole number, e.g. `one thousand two hundred`.
+fn number_in_words(mut n: u64) -> String {
+    if n == 0 {
+        return ONES[0].to_string();
+    }
+    let mut words = Vec::new();
+    for (scale, name) in SCALES {
+        if n >= scale {
+            words.push(format!("{} {name}", hundreds_in_words(n / scale)));
+            n %= scale;
+        }
+    }
+    if n > 0 {
+        words.push(hundreds_in_words(n));
+    }
+    words.join(" ")
+}
+
+/// Spells out the glow amount in whole tokens, with the decimals read digit by digit, e.g.
+/// `one thousand two hundred point five IOTA`.
+pub fn amount_in_words(glow: u64, network: Network) -> String {
+    let scale = 10u64.pow(DisplayUnit::Iota.exponent());
+    let mut words = number_in_words(glow / scale);
+    let fraction = format!("{:06}", glow % scale);
+    let fraction = fraction.trim_end_matches('0');
+    if !fraction.is_empty() {
+        words.push_str(" point");
+        for digit in fraction.bytes() {
+            words.push(' ');
+            words.push_str(ONES[usize::from(digit - b'0')]);
+        }
+    }
+    format!("{words} {}", network.token_symbol())
+}
@@ -0,0 +1,107 @@
+//! Parsing of the private keys in the supported encodings.
+
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use bech32::FromBase32;
+use clap::ValueEnum;
+use iota_sdk::client::secret::{private_key::PrivateKeySecretManager, SecretManager};
+
+use crate::{credentials, encrypted_keys, node::NodeArgs};
+
+/// Length of an Ed25519 private key in bytes.
+const KEY_LENGTH: usize = 32;
+
+/// Command line options of the private keys.
+#[derive(clap::Args, Debug)]
+pub struct KeyArgs {
+    /// Private keys, encoded as given by `--key-format`
+    #[arg(long, value_delimiter = ',', env = "PRIVATE_KEYS")]
+    pub keys: Vec<String>,
+
+    /// Encoding of all private keys, including those of the keyring and the encrypted file
+    #[arg(long, value_enum, default_value_t = KeyFormat::Base58)]
+    pub key_format: KeyFormat,
+
+    /// Name of an OS keyring entry to read the node URLs and keys from, created on first use
+    #[arg(long, env = "KEYRING_ENTRY")]
+    keyring_entry: Option<String>,
+
+    /// Passphrase encrypted age file with additional keys, the passphrase is read from
+    /// `KEYS_PASSPHRASE` or prompted for
+    #[arg(long, env = "KEYS_ENCRYPTED")]
+    keys_encrypted: Option<PathBuf>,
+
+    /// Only process the first n keys
+    #[arg(long)]
+    max_keys: Option<usize>,
+}
+
+impl KeyArgs {
+    /// Adds the keys of the keyring entry and the encrypted file, then keeps the first
+    /// `--max-keys`. The keyring entry also provides the node URLs if none were given.
+    pub fn load(&mut self, node: &mut NodeArgs) -> Result<()> {
+        if let Some(name) = &self.keyring_entry {
+            // explicitly provided node URLs and keys take precedence
+            let credentials = credentials::load_or_prompt(name)?;
+            if node.node_url.is_empty() {
+                node.node_url = credentials.node_url;
+            }
+            if self.keys.is_empty() {
+                self.keys = credentials.keys;
+            }
+        }
+        if let Some(path) = &self.keys_encrypted {
+            self.keys.extend(encrypted_keys::read(path)?);
+        }
+        if let Some(max_keys) = self.max_keys {
+            self.keys.truncate(max_keys);
+        }
+
+        Ok(())
+    }
+}
+
+/// Encoding of the private keys.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum KeyFormat {
+    Base58,
+    /// Hex, with or without the `0x` prefix
+    Hex,
+    /// Bech32 with any human readable part
+    Bech32,
+}
+
+impl KeyFormat {
+    /// Decodes the private key and creates a secret manager for it.
+    pub fn secret_manager(self, key: &str) -> Result<SecretManager> {
+        let secret_manager = match self {
+            Self::Base58 => PrivateKeySecretManager::try_from_b58(key)
+                .context("invalid base58 private key, expected 32 bytes")?,
+            Self::Hex => {
+                let hex = key.strip_prefix("0x").unwrap_or(key);
+                ensure!(
+                    hex.len() == 2 * KEY_LENGTH,
+                    "invalid hex private key, expected {} hex digits but got {}",
+                    2 * KEY_LENGTH,
+                    hex.len()
+                );
+                PrivateKeySecretManager::try_from_hex(format!("0x{hex}"))
+                    .context("invalid hex private key, expected only hex digits")?
+            }
+            Self::Bech32 => {
+                let (_, data, _) = bech32::decode(key).context("invalid bech32 private key")?;
+                let bytes = Vec::<u8>::from_base32(&data).context("invalid bech32 private key")?;
+                ensure!(
+                    bytes.len() == KEY_LENGTH,
+                    "invalid bech32 private key, expected {KEY_LENGTH} bytes but got {}",
+                    bytes.len()
+                );
+                let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+                PrivateKeySecretManager::try_from_hex(format!("0x{hex}"))?
+            }
+        };
+
+        Ok(SecretManager::from(secret_manager))
+    }
+}
@@ -0,0 +1,34 @@
+//! Code shared by `send_all` and `timed_balance`: the keys and their addresses, the node clients
+//! and their outputs, the display of amounts, the start of the runtime and the parsing of the
+//! common command line options.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+
+pub mod addresses;
+pub mod amount;
+pub mod credentials;
+pub mod encrypted_keys;
+pub mod format;
+pub mod keys;
+pub mod network;
+pub mod node;
+pub mod outputs;
+pub mod runtime;
+pub mod webhook;
+
+/// Parses a UTC date like `2025-01-31`, a date and time like `2025-01-31 12:00:00` or an RFC 3339
+/// timestamp into a unix timestamp.
+pub fn parse_datetime(s: &str) -> Result<u32> {
+    let s = s.trim();
+    let datetime = DateTime::parse_from_rfc3339(s)
+        .map(|datetime| datetime.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|date| date.and_time(NaiveTime::MIN))
+        })
+        .with_context(|| format!("invalid date '{s}'"))?;
+
+    u32::try_from(datetime.and_utc().timestamp())
+        .with_context(|| format!("date '{s}' is out of range"))
+}
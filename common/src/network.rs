@@ -0,0 +1,55 @@
+//! Presets of the parameters that differ between networks.
+
+use clap::ValueEnum;
+use iota_sdk::types::block::protocol::ProtocolParameters;
+use serde::{Deserialize, Serialize};
+
+/// Network the tool is used on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Network {
+    Iota,
+    Shimmer,
+}
+
+impl Network {
+    /// Id of the base token on CoinGecko.
+    pub fn coin_id(self) -> &'static str {
+        match self {
+            Self::Iota => "iota",
+            Self::Shimmer => "shimmer",
+        }
+    }
+
+    /// Symbol of the base token, which has 6 decimals on both networks.
+    pub fn token_symbol(self) -> &'static str {
+        match self {
+            Self::Iota => "IOTA",
+            Self::Shimmer => "SMR",
+        }
+    }
+}
+
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Network a run was made on, as reported by the node.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetworkInfo {
+    pub network_name: String,
+    pub bech32_hrp: String,
+    pub protocol_version: u8,
+}
+
+impl From<&ProtocolParameters> for NetworkInfo {
+    fn from(protocol_parameters: &ProtocolParameters) -> Self {
+        Self {
+            network_name: protocol_parameters.network_name().to_string(),
+            bech32_hrp: protocol_parameters.bech32_hrp().to_string(),
+            protocol_version: protocol_parameters.protocol_version(),
+        }
+    }
+}
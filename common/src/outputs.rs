@@ -0,0 +1,72 @@
+//! Handling of the outputs returned by the nodes.
+
+use iota_sdk::types::block::output::OutputWithMetadata;
+
+/// Sorts the outputs by their id, as the node returns them in no guaranteed order.
+pub fn sort_by_output_id(outputs: &mut [OutputWithMetadata]) {
+    outputs.sort_by_key(|output| *output.metadata().output_id());
+}
+
+#[cfg(test)]
+mod tests {
+    use iota_sdk::types::block::{
+        address::Ed25519Address,
+        output::{
+            unlock_condition::AddressUnlockCondition, BasicOutputBuilder, OutputId, OutputMetadata,
+        },
+        payload::transaction::TransactionId,
+        BlockId,
+    };
+
+    use super::*;
+
+    /// Returns outputs with the ids of the transaction and output indexes, in the given order.
+    fn outputs_with_ids(ids: &[(u8, u16)]) -> Vec<OutputWithMetadata> {
+        ids.iter()
+            .map(|&(transaction, index)| {
+                let output_id =
+                    OutputId::new(TransactionId::new([transaction; 32]), index).unwrap();
+                let metadata = OutputMetadata::new(
+                    BlockId::new([0; 32]),
+                    output_id,
+                    false,
+                    None,
+                    None,
+                    None,
+                    0,
+                    0,
+                    0,
+                );
+                let output = BasicOutputBuilder::new_with_amount(1_000_000)
+                    .add_unlock_condition(AddressUnlockCondition::new(Ed25519Address::new([1; 32])))
+                    .finish_output(1_813_620_509_061_365)
+                    .unwrap();
+                OutputWithMetadata::new(output, metadata)
+            })
+            .collect()
+    }
+
+    fn sorted_ids(mut outputs: Vec<OutputWithMetadata>) -> Vec<OutputId> {
+        sort_by_output_id(&mut outputs);
+        outputs
+            .iter()
+            .map(|output| *output.metadata().output_id())
+            .collect()
+    }
+
+    #[test]
+    fn outputs_are_sorted_by_output_id_regardless_of_the_input_order() {
+        let expected: Vec<_> = outputs_with_ids(&[(1, 0), (1, 1), (2, 0), (3, 5)])
+            .iter()
+            .map(|output| *output.metadata().output_id())
+            .collect();
+        let shuffled = [
+            [(3, 5), (1, 1), (2, 0), (1, 0)],
+            [(2, 0), (3, 5), (1, 0), (1, 1)],
+            [(1, 1), (1, 0), (3, 5), (2, 0)],
+        ];
+        for ids in shuffled {
+            assert_eq!(sorted_ids(outputs_with_ids(&ids)), expected);
+        }
+    }
+}
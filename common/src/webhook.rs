@@ -0,0 +1,71 @@
+//! Posting the JSON summary of a run to a webhook.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+
+/// Command line options of the webhook.
+#[derive(clap::Args, Debug)]
+pub struct WebhookArgs {
+    /// URL to POST the JSON summary to after the run
+    #[arg(
+        id = "webhook",
+        long = "webhook",
+        value_name = "URL",
+        env = "WEBHOOK_URL"
+    )]
+    pub url: Option<String>,
+
+    /// Bearer token to authenticate with the webhook
+    #[arg(
+        long,
+        env = "WEBHOOK_TOKEN",
+        requires = "webhook",
+        conflicts_with = "webhook_user"
+    )]
+    webhook_token: Option<String>,
+
+    /// User name for basic authentication with the webhook
+    #[arg(long, env = "WEBHOOK_USER", requires_all = ["webhook", "webhook_password"])]
+    webhook_user: Option<String>,
+
+    /// Password for basic authentication with the webhook
+    #[arg(long, env = "WEBHOOK_PASSWORD", requires = "webhook_user")]
+    webhook_password: Option<String>,
+
+    /// Timeout of the webhook request in seconds
+    #[arg(long, default_value_t = 10)]
+    webhook_timeout: u64,
+}
+
+impl WebhookArgs {
+    /// Posts the summary to the webhook, if any, only warning on failure as the run itself
+    /// already succeeded.
+    pub async fn notify(&self, summary: &impl Serialize) {
+        let Some(url) = &self.url else {
+            return;
+        };
+        if let Err(err) = self.post(url, summary).await {
+            eprintln!("Warning: failed to post the summary to the webhook: {err:#}");
+        }
+    }
+
+    async fn post(&self, url: &str, summary: &impl Serialize) -> Result<()> {
+        let mut request = reqwest::Client::new()
+            .post(url)
+            .timeout(Duration::from_secs(self.webhook_timeout))
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(summary)?);
+        if let Some(token) = &self.webhook_token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(user) = &self.webhook_user {
+            request = request.basic_auth(user, self.webhook_password.as_ref());
+        }
+        request.send().await?.error_for_status()?;
+
+        Ok(())
+    }
+}
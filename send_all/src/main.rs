@@ -1,72 +1,520 @@
-use anyhow::Result;
-use clap::Parser;
+use std::{
+    collections::HashMap,
+    io::IsTerminal,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use chrono::DateTime;
+use clap::{Parser, ValueEnum};
+use dialoguer::{Confirm, MultiSelect};
 use iota_sdk::{
     client::{
-        api::GetAddressesOptions,
         node_api::indexer::query_parameters::QueryParameter,
         secret::{private_key::PrivateKeySecretManager, SecretManager},
         Client,
     },
-    types::block::address::Bech32Address,
-    types::block::output::{unlock_condition::AddressUnlockCondition, BasicOutputBuilder},
+    crypto::hashes::{blake2b::Blake2b256, Digest},
+    types::block::address::{Bech32Address, Hrp},
+    types::block::input::UtxoInput,
+    types::block::output::{
+        unlock_condition::{
+            AddressUnlockCondition, ExpirationUnlockCondition, StorageDepositReturnUnlockCondition,
+            TimelockUnlockCondition,
+        },
+        BasicOutputBuilder, NftId, NftOutputBuilder, Output, Rent, RentStructure, OUTPUT_COUNT_MAX,
+    },
+    types::block::{payload::TaggedDataPayload, BlockId},
+};
+use serde::{Deserialize, Serialize};
+
+use common::{
+    addresses,
+    amount::Amount,
+    format::{self, AmountFormat, DisplayUnit, Rounding},
+    keys::KeyArgs,
+    network::{Network, NetworkInfo},
+    node::NodeArgs,
+    outputs::sort_by_output_id,
+    parse_datetime,
+    runtime::{self, RuntimeArgs},
+    webhook::WebhookArgs,
 };
 
+use self::progress::{KeyRecord, Progress};
+
+mod progress;
+mod receipt;
+mod state;
+
+/// Prints a status line to stdout, or to stderr if stdout is reserved for the JSON summary.
+macro_rules! status {
+    ($json:expr, $($arg:tt)*) => {
+        if $json {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
 /// Simple program to send all unlocked fonds of a list of private keys to a designated address.
 #[derive(Parser, Debug)]
-#[command(about, long_about = None)]
+#[command(
+    about,
+    long_about = None,
+    mut_arg("node_url", |arg| {
+        arg.required_unless_present_any(["keyring_entry", "derive", "validate_keys_only"])
+    })
+)]
 struct Args {
-    /// Node URL to issue transactions with
-    #[arg(short, long, env = "NODE_URL")]
-    node_url: String,
+    #[command(flatten)]
+    node: NodeArgs,
 
-    /// Base58 encoded private keys
-    #[arg(long, value_delimiter = ',', env = "PRIVATE_KEYS")]
-    keys: Vec<String>,
+    #[command(flatten)]
+    key: KeyArgs,
 
     /// Recipient address
-    #[arg(long, env = "RECIPIENT_ADDRESS")]
-    recipient_address: Bech32Address,
+    #[arg(long, env = "RECIPIENT_ADDRESS", required_unless_present_any = ["derive", "validate_keys_only", "print_addresses", "count_only"])]
+    recipient_address: Option<Bech32Address>,
+
+    /// Print the address derived from each key and exit, to compare the derivation of both tools
+    #[arg(long)]
+    print_addresses: bool,
+
+    /// Print the address derived from each key for `--hrp`, one per line, and exit without
+    /// contacting a node
+    #[arg(long, requires = "hrp", conflicts_with = "print_addresses")]
+    derive: bool,
+
+    /// Check that every key parses and derives an address for `--hrp`, print the number of valid
+    /// keys and exit without contacting a node
+    #[arg(long, requires = "hrp", conflicts_with_all = ["print_addresses", "derive"])]
+    validate_keys_only: bool,
+
+    /// Human readable part of the derived addresses, e.g. `iota` or `smr`
+    #[arg(long)]
+    hrp: Option<Hrp>,
+
+    /// Only consider the first n outputs of each address
+    #[arg(long)]
+    max_outputs: Option<usize>,
+
+    /// Only process keys deriving to one of these addresses
+    #[arg(long, value_delimiter = ',')]
+    only_addresses: Vec<Bech32Address>,
+
+    /// File containing the allowed recipient addresses, one per line
+    #[arg(long)]
+    allowed_recipients: Option<PathBuf>,
+
+    /// Print a JSON summary of the sweeps to stdout
+    #[arg(long)]
+    json: bool,
+
+    /// Indent the JSON output for reading instead of printing it on a single line
+    #[arg(long, requires = "json")]
+    json_pretty: bool,
+
+    /// Only sweep expired outputs whose expiration return address is ours
+    #[arg(long)]
+    only_expired: bool,
+
+    /// How basic outputs with a storage deposit return are treated
+    #[arg(long, value_enum, default_value_t = StorageDepositReturn::Exclude)]
+    storage_deposit_return: StorageDepositReturn,
+
+    /// Also transfer the NFTs owned by the addresses to the recipient
+    #[arg(long, conflicts_with = "only_expired")]
+    include_nfts: bool,
+
+    /// Show a short fingerprint after each address to compare it at a glance
+    #[arg(long)]
+    show_fingerprint: bool,
+
+    /// Unit to display amounts in
+    #[arg(long, value_enum, default_value_t = DisplayUnit::Iota)]
+    unit: DisplayUnit,
+
+    /// Network preset for the token symbol
+    #[arg(long, value_enum, default_value_t = Network::Iota)]
+    network: Network,
+
+    /// Number of decimals to display amounts with, defaults to all decimals of the unit
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=18))]
+    decimals: Option<u32>,
+
+    /// How to round displayed amounts, this does not affect the amounts that are sent
+    #[arg(long, value_enum, default_value_t = Rounding::Nearest)]
+    rounding: Rounding,
+
+    /// Ask for confirmation before sweeping, with the total spelled out in words
+    #[arg(long)]
+    confirm_words: bool,
+
+    /// Order the keys are swept in, e.g. the largest balances first in case the run is interrupted
+    #[arg(long, value_enum, default_value_t = SweepOrder::Input)]
+    order: SweepOrder,
+
+    /// Interactively select the addresses to sweep
+    #[arg(long)]
+    interactive: bool,
+
+    /// Do not print the keys without funds, they are still counted in the summary
+    #[arg(long)]
+    quiet_skips: bool,
+
+    /// Do not print progress while waiting for the blocks to be included
+    #[arg(long)]
+    quiet: bool,
+
+    /// Print one JSON line per processed key to stderr with its index, address, status and amounts,
+    /// for supervising processes
+    #[arg(long)]
+    progress_json: bool,
+
+    /// File to append a JSON line to for every processed key as it completes, with the amounts
+    /// found and sent, the block, the status and the duration
+    #[arg(long, value_name = "PATH")]
+    per_key_out: Option<PathBuf>,
+
+    /// Keep sweeping the other keys when a sweep fails, listing the failed ones at the end
+    #[arg(long)]
+    continue_on_error: bool,
+
+    /// Retry the failed sweeps once after all keys were processed
+    #[arg(long, requires = "continue_on_error")]
+    retry_failed: bool,
+
+    /// Only print the number of outputs per address without fetching them or sending anything
+    ///
+    /// Time-locked and expired outputs are included, as they can only be told apart by fetching
+    /// the outputs.
+    #[arg(long)]
+    count_only: bool,
+
+    /// Skip addresses with less funds, e.g. `1.5IOTA`, `500kglow` or `1000` glow
+    #[arg(long)]
+    min_amount: Option<Amount>,
+
+    /// File of `<address> <amount>` lines overriding `--min-amount` for these source addresses
+    #[arg(long)]
+    min_amount_file: Option<PathBuf>,
+
+    /// Sweep at most this amount per address, e.g. `1.5IOTA`, `500kglow` or `1000` glow
+    ///
+    /// Like with `--sweep-percent`, less is swept if the remaining funds would be below the storage
+    /// deposit.
+    #[arg(long)]
+    max_amount: Option<Amount>,
+
+    /// Only sweep this percentage of each address's funds, the rest remains at the address
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    sweep_percent: Option<u8>,
+
+    /// Let unclaimed funds return to the source address after this duration, e.g. `7d` or `12h`
+    #[arg(long, value_parser = parse_duration)]
+    expiration: Option<Duration>,
+
+    /// Time-lock the sent funds until this UTC time, e.g. `2025-01-31` or `2025-01-31T12:00:00Z`
+    #[arg(long, value_parser = parse_datetime)]
+    recipient_timelock: Option<u32>,
+
+    /// Split the funds of each key into this many equal outputs, the remainder goes into the first
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..=i64::from(OUTPUT_COUNT_MAX))
+    )]
+    split_into: u16,
+
+    /// Let the storage deposit of each recipient output return to the source address when the
+    /// recipient spends it
+    ///
+    /// By default the whole amount is transferred, and the part locked as storage deposit is
+    /// reclaimed by the recipient when they spend the output. With this option the recipient can
+    /// only spend an output in a transaction returning the deposit to the source, which not all
+    /// wallets support, the output itself needs a larger deposit, and the source address must be
+    /// kept to receive the deposit back.
+    #[arg(long)]
+    return_storage_deposit: bool,
+
+    /// Tag of a tagged data payload attached to each sweep, e.g. an invoice id
+    #[arg(long, value_parser = parse_tag)]
+    tag: Option<String>,
+
+    /// Data of a tagged data payload attached to each sweep, hex if prefixed with `0x`, UTF-8
+    /// otherwise
+    #[arg(long)]
+    data: Option<String>,
+
+    /// File recording the completed sweeps, addresses found in it are skipped
+    #[arg(long)]
+    state_file: Option<PathBuf>,
+
+    /// Stop starting new sweeps after this many seconds since the start, finishing the current one
+    /// and exiting with code 124 after writing the summary
+    ///
+    /// Together with `--state-file`, the next run continues with the remaining keys.
+    #[arg(long, value_name = "SECONDS")]
+    deadline: Option<u64>,
+
+    /// File to write the JSON summary to after the run
+    #[arg(long)]
+    summary_out: Option<PathBuf>,
+
+    /// Also rewrite the summary file with the progress so far after every this many processed keys,
+    /// whether skipped, swept or failed
+    #[arg(long, requires = "summary_out")]
+    checkpoint_every: Option<NonZeroUsize>,
+
+    /// Base58 encoded private key to sign the summary file with, writing the signature to
+    /// `<summary file>.sig`
+    #[arg(long, env = "RECEIPT_KEY", requires = "summary_out")]
+    receipt_key: Option<String>,
+
+    #[command(flatten)]
+    runtime: RuntimeArgs,
+
+    #[command(flatten)]
+    webhook: WebhookArgs,
+}
+
+impl Args {
+    /// Returns the format to display amounts with.
+    fn amount_format(&self) -> AmountFormat {
+        AmountFormat {
+            network: self.network,
+            unit: self.unit,
+            decimals: self.decimals.unwrap_or(self.unit.exponent()),
+            rounding: self.rounding,
+        }
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    dotenvy::dotenv()?;
+fn main() -> Result<()> {
+    runtime::load_dotenv()?;
     let args = Args::parse();
+    args.runtime.build_runtime()?.block_on(run(args))
+}
 
-    // Create a node client
-    let client = Client::builder()
-        .with_node(&args.node_url)?
-        .finish()
-        .await?;
+/// Exit code of a run stopped by `--deadline`, the same as of `timeout`.
+const DEADLINE_EXIT_CODE: i32 = 124;
+
+async fn run(mut args: Args) -> Result<()> {
+    let start = Instant::now();
+    args.key.load(&mut args.node)?;
+
+    if let Some(key) = &args.receipt_key {
+        // fail before sending anything rather than when signing the summary
+        PrivateKeySecretManager::try_from_b58(key).context("invalid receipt key")?;
+    }
+    let tag = args.tag.as_ref().map(|tag| tag.as_bytes().to_vec());
+    let data = args.data.as_deref().map(parse_data).transpose()?;
 
-    let token_supply = client.get_token_supply().await?;
+    if let (true, Some(hrp)) = (args.derive, args.hrp) {
+        return addresses::print_derived(&args.key.keys, args.key.key_format, hrp).await;
+    }
+    if let (true, Some(hrp)) = (args.validate_keys_only, args.hrp) {
+        return addresses::validate(&args.key.keys, args.key.key_format, hrp).await;
+    }
+    // Create the node clients
+    let clients = args.node.build_clients().await?;
+    let client = &clients[0];
+
+    if args.print_addresses {
+        return addresses::print_addresses(&clients, &args.key.keys, args.key.key_format).await;
+    }
     let now = client.get_time_checked().await?;
+    if args.count_only {
+        return print_output_counts(&args, &clients, now).await;
+    }
 
-    for base58 in args.keys {
-        let secret_manager = SecretManager::from(PrivateKeySecretManager::try_from_b58(base58)?);
+    let recipient_address = args
+        .recipient_address
+        .context("the recipient address is required")?;
+    if let Some(path) = &args.allowed_recipients {
+        let allowed = read_addresses(path)?;
+        ensure!(
+            allowed.contains(&recipient_address),
+            "recipient address {} is not in the allowed recipients of '{}'",
+            recipient_address,
+            path.display()
+        );
+    }
 
-        // Generate the first address
-        let mut addresses = secret_manager
-            .generate_ed25519_addresses(
-                GetAddressesOptions::from_client(&client)
-                    .await?
-                    .with_account_index(0)
-                    .with_range(0..1),
-            )
-            .await?;
-        let address = addresses.pop().unwrap();
+    let protocol_parameters = client.get_protocol_parameters().await?;
+    let token_supply = protocol_parameters.token_supply();
+    let rent_structure = *protocol_parameters.rent_structure();
+    // fail before processing any key if the recipient cannot receive the outputs
+    ensure!(
+        recipient_address.hrp() == protocol_parameters.bech32_hrp(),
+        "recipient {recipient_address} is not an address of the node's network, expected the HRP '{}'",
+        protocol_parameters.bech32_hrp()
+    );
+    BasicOutputBuilder::new_with_minimum_storage_deposit(rent_structure)
+        .add_unlock_condition(AddressUnlockCondition::new(recipient_address))
+        .finish_output(token_supply)
+        .with_context(|| format!("recipient {recipient_address} cannot receive a basic output"))?;
+    // the deposit of a plain output to the source, which is the same for all keys; it is computed
+    // with the recipient address, as all address kinds have the same serialized size
+    let return_deposit = args
+        .return_storage_deposit
+        .then(|| {
+            BasicOutputBuilder::new_with_minimum_storage_deposit(rent_structure)
+                .add_unlock_condition(AddressUnlockCondition::new(recipient_address))
+                .finish()
+                .map(|output| output.amount())
+        })
+        .transpose()?;
+    let expires_at = args
+        .expiration
+        .map(|expiration| {
+            u32::try_from(expiration.as_secs())
+                .ok()
+                .and_then(|secs| now.checked_add(secs))
+                .context("expiration is too far in the future")
+        })
+        .transpose()?;
+    if let Some(unlocks_at) = args.recipient_timelock {
+        ensure!(unlocks_at > now, "recipient timelock must be in the future");
+        ensure!(
+            expires_at.is_none_or(|expires_at| unlocks_at < expires_at),
+            "recipient timelock must be before the expiration"
+        );
+    }
 
-        // Get output ids of outputs that can be controlled by this address without further unlock constraints
-        let output_ids_response = client
-            .basic_output_ids([
-                QueryParameter::Address(address),
-                QueryParameter::HasStorageDepositReturn(false),
-            ])
-            .await?;
+    let base_token = client.get_info().await?.node_info.base_token;
+    let min_amount = args
+        .min_amount
+        .as_ref()
+        .map(|amount| amount.to_glow(&base_token))
+        .transpose()?;
+    let min_amounts = match &args.min_amount_file {
+        Some(path) => read_min_amounts(path)?
+            .into_iter()
+            .map(|(address, amount)| Ok((address, amount.to_glow(&base_token)?)))
+            .collect::<Result<HashMap<_, _>>>()?,
+        None => HashMap::new(),
+    };
+    let max_amount = args
+        .max_amount
+        .as_ref()
+        .map(|amount| amount.to_glow(&base_token))
+        .transpose()?;
+
+    // Stop starting new sweeps on the first Ctrl-C, exit immediately on the second
+    let shutdown = Arc::new(AtomicBool::new(false));
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if shutdown.swap(true, Ordering::SeqCst) {
+                    eprintln!("Forced exit");
+                    std::process::exit(130);
+                }
+                eprintln!("Finishing the current sweep, press Ctrl-C again to exit immediately");
+            }
+        }
+    });
+    let deadline_reached = Arc::new(AtomicBool::new(false));
+    if let Some(secs) = args.deadline {
+        let shutdown = shutdown.clone();
+        let deadline_reached = deadline_reached.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep_until((start + Duration::from_secs(secs)).into()).await;
+            deadline_reached.store(true, Ordering::SeqCst);
+            shutdown.store(true, Ordering::SeqCst);
+            eprintln!("Deadline of {secs}s reached, finishing the current sweep");
+        });
+    }
+
+    let already_swept = match &args.state_file {
+        Some(path) => state::read_completed(path)?,
+        None => Default::default(),
+    };
+
+    let mut progress = Progress::new(args.progress_json, args.per_key_out.as_deref())?;
+    let num_keys = args.key.keys.len();
+    let mut summary = Summary {
+        network: (&protocol_parameters).into(),
+        recipient_address,
+        only_expired: args.only_expired,
+        num_keys,
+        keys_processed: 0,
+        keys_with_funds: 0,
+        num_sweeps: 0,
+        interrupted: false,
+        deadline_reached: false,
+        total_amount: 0,
+        sweeps: Vec::new(),
+        failed: Vec::new(),
+    };
+    let mut candidates = Vec::new();
+    for (i, key) in std::mem::take(&mut args.key.keys).into_iter().enumerate() {
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        let client = &clients[i % clients.len()];
+        let lookup_start = Instant::now();
 
-        let outputs_responses = client.get_outputs(&output_ids_response.items).await?;
+        let secret_manager = args.key.key_format.secret_manager(&key)?;
+
+        let address = addresses::first_address(client, i, &secret_manager).await?;
+        if !args.only_addresses.is_empty() && !args.only_addresses.contains(&address) {
+            record_key(
+                &mut summary,
+                &mut progress,
+                &KeyRecord::skipped(i, address, 0, lookup_start.elapsed()),
+                &args,
+            )?;
+            continue;
+        }
+        if already_swept.contains(&address) {
+            status!(
+                args.json,
+                "Skipping {} already swept in a previous run",
+                address_label(&address, args.show_fingerprint)
+            );
+            record_key(
+                &mut summary,
+                &mut progress,
+                &KeyRecord::skipped(i, address, 0, lookup_start.elapsed()),
+                &args,
+            )?;
+            continue;
+        }
+
+        let mut output_ids = client
+            .basic_output_ids(query_parameters(&args, address, now))
+            .await?
+            .items;
+        // the node does not guarantee an order, sort for reproducible processing
+        output_ids.sort();
+        let capped = args.max_outputs.filter(|&max| output_ids.len() > max);
+        if let Some(max_outputs) = capped {
+            eprintln!(
+                "Warning: {} has {} outputs, only sweeping the first {max_outputs}",
+                address,
+                output_ids.len()
+            );
+            output_ids.truncate(max_outputs);
+        }
+
+        let mut outputs_responses = client.get_outputs(&output_ids).await?;
+        sort_by_output_id(&mut outputs_responses);
 
         let mut total_amount = 0;
+        let mut inputs = Vec::new();
+        let mut locked_amount = 0;
+        let mut next_unlock = None;
         for output in outputs_responses {
             let metadata = output.metadata();
             if metadata.is_spent() {
@@ -74,45 +522,1063 @@ async fn main() -> Result<()> {
             }
 
             let output = output.output();
+            match eligibility(output, &address, now, args.only_expired) {
+                Eligibility::Locked(timestamp) => {
+                    locked_amount += net_amount(output, now);
+                    next_unlock =
+                        Some(next_unlock.map_or(timestamp, |next: u32| next.min(timestamp)));
+                }
+                Eligibility::Sweepable => {
+                    total_amount += net_amount(output, now);
+                    // the automatic input selection skips outputs with a storage deposit return
+                    // and would consume all outputs of the address instead of the capped ones
+                    if args.only_expired
+                        || args.storage_deposit_return == StorageDepositReturn::IncludeNet
+                        || capped.is_some()
+                    {
+                        inputs.push(UtxoInput::from(*metadata.output_id()));
+                    }
+                }
+                Eligibility::Ignored => {}
+            }
+        }
+
+        if let Some(next_unlock) = next_unlock {
+            let next_unlock =
+                DateTime::from_timestamp(next_unlock.into(), 0).context("invalid timestamp")?;
+            status!(
+                args.json,
+                "{} still has {} time-locked, the next unlocks at {next_unlock}",
+                address_label(&address, args.show_fingerprint),
+                args.amount_format().format(locked_amount)
+            );
+        }
 
-            let locked = output
-                .unlock_conditions()
-                .map_or(false, |uc| uc.is_time_locked(now));
-            let expired = output
-                .unlock_conditions()
-                .map_or(false, |uc| uc.is_expired(now));
+        let nfts = if args.include_nfts {
+            nft_outputs(client, address, recipient_address, now, token_supply).await?
+        } else {
+            Vec::new()
+        };
 
-            if !locked && !expired {
-                total_amount += output.amount();
+        if total_amount == 0 && nfts.is_empty() {
+            if !args.quiet_skips {
+                status!(
+                    args.json,
+                    "No funds to send from {}",
+                    address_label(&address, args.show_fingerprint)
+                );
             }
+            record_key(
+                &mut summary,
+                &mut progress,
+                &KeyRecord::skipped(i, address, 0, lookup_start.elapsed()),
+                &args,
+            )?;
+            continue;
         }
-        if total_amount == 0 {
-            println!("No funds to send from {}", address);
+        summary.keys_with_funds += 1;
+        let min_amount = min_amounts.get(&address).copied().or(min_amount);
+        if let Some(min_amount) = min_amount.filter(|&min_amount| total_amount < min_amount) {
+            status!(
+                args.json,
+                "Skipping {} with only {}, below {}",
+                address_label(&address, args.show_fingerprint),
+                args.amount_format().format(total_amount),
+                args.amount_format().format(min_amount)
+            );
+            record_key(
+                &mut summary,
+                &mut progress,
+                &KeyRecord::skipped(i, address, total_amount, lookup_start.elapsed()),
+                &args,
+            )?;
             continue;
         }
 
-        println!(
-            "Sending {:.6} IOTA from {} to {}",
-            total_amount as f64 / 1_000_000.0,
+        let mut amount = total_amount;
+        if let Some(percent) = args.sweep_percent {
+            // Round down, the remaining glow stay at the address
+            amount = (amount as u128 * percent as u128 / 100) as u64;
+        }
+        if let Some(max_amount) = max_amount {
+            amount = amount.min(max_amount);
+        }
+        if amount < total_amount {
+            // The remainder output must cover its storage deposit
+            let min_deposit = BasicOutputBuilder::new_with_minimum_storage_deposit(rent_structure)
+                .add_unlock_condition(AddressUnlockCondition::new(address))
+                .finish()?
+                .amount();
+            let remainder = total_amount - amount;
+            if remainder < min_deposit {
+                amount = total_amount.saturating_sub(min_deposit);
+            }
+            if amount == 0 && nfts.is_empty() {
+                status!(
+                    args.json,
+                    "Skipping {}, the remaining {} would be below the storage deposit",
+                    address_label(&address, args.show_fingerprint),
+                    args.amount_format().format(total_amount)
+                );
+                record_key(
+                    &mut summary,
+                    &mut progress,
+                    &KeyRecord::skipped(i, address, total_amount, lookup_start.elapsed()),
+                    &args,
+                )?;
+                continue;
+            }
+        }
+        if (expires_at.is_some()
+            || args.recipient_timelock.is_some()
+            || args.split_into > 1
+            || return_deposit.is_some())
+            && amount > 0
+        {
+            // additional unlock conditions increase the required storage deposit, and each part of
+            // a split must cover it on its own
+            let output = sweep_output(
+                amount / u64::from(args.split_into),
+                recipient_address,
+                address,
+                expires_at,
+                args.recipient_timelock,
+                return_deposit,
+                token_supply,
+            )?;
+            if output
+                .verify_storage_deposit(rent_structure, token_supply)
+                .is_err()
+            {
+                status!(
+                    args.json,
+                    "Skipping {}, {} split into {} is below the storage deposit of an output with these unlock conditions",
+                    address_label(&address, args.show_fingerprint),
+                    args.amount_format().format(amount),
+                    args.split_into
+                );
+                record_key(
+                    &mut summary,
+                    &mut progress,
+                    &KeyRecord::skipped(i, address, total_amount, lookup_start.elapsed()),
+                    &args,
+                )?;
+                continue;
+            }
+        }
+
+        candidates.push(Candidate {
+            key_index: i,
+            found: total_amount,
+            lookup_time: lookup_start.elapsed(),
+            secret_manager,
             address,
-            args.recipient_address
+            amount,
+            min_amount,
+            inputs: with_nft_inputs(inputs, &nfts),
+            nfts,
+        });
+    }
+    status!(
+        args.json,
+        "{} of {num_keys} keys had {}",
+        summary.keys_with_funds,
+        if args.only_expired {
+            "expired outputs returning to them"
+        } else {
+            "funds"
+        }
+    );
+
+    if args.interactive {
+        candidates = select_candidates(candidates, args.show_fingerprint, args.amount_format())?;
+    }
+
+    match args.order {
+        SweepOrder::Input => {}
+        SweepOrder::BalanceDesc => candidates.sort_by_key(|c| std::cmp::Reverse(c.amount)),
+        SweepOrder::BalanceAsc => candidates.sort_by_key(|c| c.amount),
+    }
+
+    if args.confirm_words && !candidates.is_empty() {
+        let total: u64 = candidates.iter().map(|c| c.amount).sum();
+        let confirmed = Confirm::new()
+            .with_prompt(format!(
+                "Sweep {} ({}) from {} keys to {}?",
+                format::amount_in_words(total, args.network),
+                args.amount_format().format(total),
+                candidates.len(),
+                address_label(&recipient_address, args.show_fingerprint)
+            ))
+            .default(false)
+            .interact()?;
+        if !confirmed {
+            status!(args.json, "Aborted, nothing was sent");
+            return Ok(());
+        }
+    }
+
+    let num_sweeps = candidates.len();
+    summary.num_sweeps = num_sweeps;
+    let transfer = Transfer {
+        recipient_address,
+        expires_at,
+        unlocks_at: args.recipient_timelock,
+        split_into: args.split_into,
+        return_deposit,
+        rent_structure,
+        token_supply,
+        tag,
+        data,
+    };
+    let mut failed = Vec::new();
+    for (i, candidate) in candidates.into_iter().enumerate() {
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        let client = &clients[i % clients.len()];
+
+        status!(
+            args.json,
+            "Sending {}{}{} from {} to {}",
+            args.amount_format().format(candidate.amount),
+            if args.only_expired {
+                " of expired outputs"
+            } else {
+                ""
+            },
+            if candidate.nfts.is_empty() {
+                String::new()
+            } else {
+                format!(" and {} NFTs", candidate.nfts.len())
+            },
+            address_label(&candidate.address, args.show_fingerprint),
+            address_label(&recipient_address, args.show_fingerprint)
+        );
+        if candidate.amount > 0 {
+            let deposit = transfer.storage_deposit(candidate.amount, candidate.address)?;
+            let reclaimed_by = if return_deposit.is_some() {
+                "returned to the source"
+            } else {
+                "reclaimable by the recipient"
+            };
+            status!(
+                args.json,
+                "Of these, {} are storage deposit {reclaimed_by} when spending and {} are net transferable",
+                args.amount_format().format(deposit),
+                args.amount_format().format(candidate.amount.saturating_sub(deposit))
+            );
+        }
+        let sweep_start = Instant::now();
+        match sweep(client, &candidate, &transfer, args.json, args.quiet).await {
+            Ok(sweep) => {
+                let record = KeyRecord::swept(&candidate, &sweep, sweep_start.elapsed());
+                record_sweep(&mut summary, sweep, &args)?;
+                record_key(&mut summary, &mut progress, &record, &args)?;
+            }
+            Err(err) if args.continue_on_error => {
+                eprintln!(
+                    "Warning: sweeping {} failed: {err:#}",
+                    address_label(&candidate.address, args.show_fingerprint)
+                );
+                summary.failed.push(candidate.address);
+                let record = KeyRecord::failed(&candidate, sweep_start.elapsed());
+                record_key(&mut summary, &mut progress, &record, &args)?;
+                failed.push(candidate);
+            }
+            Err(err) => {
+                summary.failed.push(candidate.address);
+                let record = KeyRecord::failed(&candidate, sweep_start.elapsed());
+                record_key(&mut summary, &mut progress, &record, &args)?;
+                return Err(err);
+            }
+        }
+    }
+
+    if args.retry_failed && !failed.is_empty() {
+        status!(args.json, "Retrying {} failed sweeps", failed.len());
+        let mut still_failed = Vec::new();
+        for (i, candidate) in failed.into_iter().enumerate() {
+            if shutdown.load(Ordering::SeqCst) {
+                still_failed.push(candidate);
+                continue;
+            }
+            let client = &clients[i % clients.len()];
+            let sweep_start = Instant::now();
+            match sweep(client, &candidate, &transfer, args.json, args.quiet).await {
+                Ok(sweep) => {
+                    status!(
+                        args.json,
+                        "Recovered {}",
+                        address_label(&candidate.address, args.show_fingerprint)
+                    );
+                    let record = KeyRecord::swept(&candidate, &sweep, sweep_start.elapsed());
+                    summary
+                        .failed
+                        .retain(|address| *address != candidate.address);
+                    record_sweep(&mut summary, sweep, &args)?;
+                    // the key was already counted as processed by its first attempt
+                    progress.record(&record)?;
+                }
+                Err(err) => {
+                    eprintln!(
+                        "Warning: retrying {} failed: {err:#}",
+                        address_label(&candidate.address, args.show_fingerprint)
+                    );
+                    progress.record(&KeyRecord::failed(&candidate, sweep_start.elapsed()))?;
+                    still_failed.push(candidate);
+                }
+            }
+        }
+        failed = still_failed;
+    }
+    for candidate in &failed {
+        status!(
+            args.json,
+            "Failed to sweep {}",
+            address_label(&candidate.address, args.show_fingerprint)
         );
+    }
+
+    summary.interrupted = shutdown.load(Ordering::SeqCst);
+    summary.deadline_reached = deadline_reached.load(Ordering::SeqCst);
+    if summary.interrupted {
+        status!(
+            args.json,
+            "Interrupted after {} of {num_sweeps} sweeps",
+            summary.sweeps.len()
+        );
+        for sweep in &summary.sweeps {
+            status!(
+                args.json,
+                "Swept {} in block {}",
+                address_label(&sweep.address, args.show_fingerprint),
+                sweep.block_id
+            );
+        }
+    }
+
+    if args.json {
+        let json = if args.json_pretty {
+            serde_json::to_string_pretty(&summary)?
+        } else {
+            serde_json::to_string(&summary)?
+        };
+        println!("{json}");
+    }
+    if let Some(path) = &args.summary_out {
+        state::write_summary(path, &summary)?;
+        if let Some(key) = &args.receipt_key {
+            let signature_path = receipt::sign(path, key).await?;
+            status!(
+                args.json,
+                "Signed the summary in {}",
+                signature_path.display()
+            );
+        }
+    }
+    args.webhook.notify(&summary).await;
+
+    if summary.deadline_reached {
+        std::process::exit(DEADLINE_EXIT_CODE);
+    }
+    Ok(())
+}
+
+/// Parameters of the outputs sent by every sweep.
+struct Transfer {
+    recipient_address: Bech32Address,
+    expires_at: Option<u32>,
+    unlocks_at: Option<u32>,
+    split_into: u16,
+    /// Amount returned to the source when the recipient spends an output, if any
+    return_deposit: Option<u64>,
+    rent_structure: RentStructure,
+    token_supply: u64,
+    tag: Option<Vec<u8>>,
+    data: Option<Vec<u8>>,
+}
+
+impl Transfer {
+    /// Returns the recipient outputs holding the amount of the source, none for a zero amount.
+    fn outputs(&self, amount: u64, source: Bech32Address) -> Result<Vec<Output>> {
+        if amount == 0 {
+            return Ok(Vec::new());
+        }
+        split_amount(amount, self.split_into)
+            .into_iter()
+            .map(|amount| {
+                sweep_output(
+                    amount,
+                    self.recipient_address,
+                    source,
+                    self.expires_at,
+                    self.unlocks_at,
+                    self.return_deposit,
+                    self.token_supply,
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the part of the amount locked as storage deposit in the recipient outputs, or the
+    /// part returned to the source if the deposit is returned.
+    fn storage_deposit(&self, amount: u64, source: Bech32Address) -> Result<u64> {
+        let outputs = self.outputs(amount, source)?;
+        Ok(match self.return_deposit {
+            Some(returned) => returned * outputs.len() as u64,
+            None => outputs
+                .iter()
+                .map(|output| output.rent_cost(&self.rent_structure))
+                .sum(),
+        })
+    }
+}
 
-        let basic_output_builder = BasicOutputBuilder::new_with_amount(total_amount)
-            .add_unlock_condition(AddressUnlockCondition::new(args.recipient_address));
-        let output = basic_output_builder.finish_output(token_supply)?;
+/// Sends the funds of the candidate and waits until the block is included.
+async fn sweep(
+    client: &Client,
+    candidate: &Candidate,
+    transfer: &Transfer,
+    json: bool,
+    quiet: bool,
+) -> Result<Sweep> {
+    let mut outputs = transfer.outputs(candidate.amount, candidate.address)?;
+    outputs.extend(candidate.nfts.iter().map(|nft| nft.output.clone()));
 
-        let block = client
-            .build_block()
-            .with_secret_manager(&secret_manager)
-            .with_outputs([output])?
-            .finish()
-            .await?;
-        println!("Block with all outputs sent: {}", block.id());
+    let mut block_builder = client
+        .build_block()
+        .with_secret_manager(&candidate.secret_manager)
+        .with_outputs(outputs)?
+        .with_tag(transfer.tag.clone())
+        .with_data(transfer.data.clone());
+    // explicitly select the inputs, so that no other outputs get consumed, the input selection
+    // adds the outputs returning their storage deposits
+    for input in &candidate.inputs {
+        block_builder = block_builder.with_input(*input)?;
+    }
+    let block = block_builder.finish().await?;
+    status!(json, "Block with all outputs sent: {}", block.id());
+
+    wait_for_inclusion(client, block.id(), json, quiet).await?;
+    Ok(Sweep {
+        address: candidate.address,
+        amount: candidate.amount,
+        min_amount: candidate.min_amount,
+        nft_ids: candidate.nfts.iter().map(|nft| nft.nft_id).collect(),
+        block_id: block.id(),
+    })
+}
 
-        let _ = client.retry_until_included(&block.id(), None, None).await?;
-        println!("Block with all outputs included: {}", block.id());
+/// Adds the completed sweep to the state file and the summary.
+fn record_sweep(summary: &mut Summary, sweep: Sweep, args: &Args) -> Result<()> {
+    if let Some(path) = &args.state_file {
+        state::append_completed(path, &sweep)?;
     }
+    summary.total_amount += sweep.amount;
+    summary.sweeps.push(sweep);
 
     Ok(())
 }
+
+/// Records the processed key, whether skipped, swept or failed, writing a checkpoint if due.
+fn record_key(
+    summary: &mut Summary,
+    progress: &mut Progress,
+    record: &KeyRecord,
+    args: &Args,
+) -> Result<()> {
+    progress.record(record)?;
+    summary.keys_processed += 1;
+
+    if let Some((path, every)) = args.summary_out.as_ref().zip(args.checkpoint_every) {
+        if summary.keys_processed.is_multiple_of(every.get()) {
+            state::write_summary(path, summary)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Interval of the progress messages while waiting for a block to be included.
+const INCLUSION_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Waits until the block is included, periodically printing that it is still waiting unless quiet.
+async fn wait_for_inclusion(
+    client: &Client,
+    block_id: BlockId,
+    json: bool,
+    quiet: bool,
+) -> Result<()> {
+    let start = Instant::now();
+    let inclusion = client.retry_until_included(&block_id, None, None);
+    tokio::pin!(inclusion);
+    let mut progress = tokio::time::interval(INCLUSION_PROGRESS_INTERVAL);
+    // the first tick completes immediately
+    progress.tick().await;
+
+    let result = loop {
+        tokio::select! {
+            result = &mut inclusion => break result,
+            _ = progress.tick() => {
+                if !quiet {
+                    status!(json, "Still waiting for inclusion of {block_id}...");
+                }
+            }
+        }
+    };
+    let elapsed = start.elapsed().as_secs();
+    result.with_context(|| format!("block {block_id} was not included after {elapsed}s"))?;
+    status!(
+        json,
+        "Block with all outputs included: {block_id} after {elapsed}s"
+    );
+
+    Ok(())
+}
+
+/// Funds of a single key that can be swept.
+struct Candidate {
+    /// Index of the key in the list of keys
+    key_index: usize,
+    /// Amount found at the address, before `--sweep-percent` and `--max-amount`
+    found: u64,
+    /// Time spent looking up the outputs of the key
+    lookup_time: Duration,
+    secret_manager: SecretManager,
+    address: Bech32Address,
+    amount: u64,
+    /// Minimum amount that applied to the address, if any
+    min_amount: Option<u64>,
+    inputs: Vec<UtxoInput>,
+    nfts: Vec<NftTransfer>,
+}
+
+/// NFT to transfer, with its output already owned by the recipient.
+struct NftTransfer {
+    nft_id: NftId,
+    /// The output currently holding the NFT
+    input: UtxoInput,
+    output: Output,
+}
+
+/// Adds the inputs of the NFTs to explicitly selected inputs, as the input selection then only
+/// uses the given ones. Without explicit inputs, the automatic selection finds the NFTs itself.
+fn with_nft_inputs(mut inputs: Vec<UtxoInput>, nfts: &[NftTransfer]) -> Vec<UtxoInput> {
+    if !inputs.is_empty() {
+        inputs.extend(nfts.iter().map(|nft| nft.input));
+    }
+    inputs
+}
+
+/// Returns the indexer filters on the basic outputs to sweep from an address.
+fn query_parameters(args: &Args, address: Bech32Address, now: u32) -> Vec<QueryParameter> {
+    let mut query_parameters = if args.only_expired {
+        // Get output ids of expired outputs that return to this address
+        vec![
+            QueryParameter::ExpirationReturnAddress(address),
+            QueryParameter::HasExpiration(true),
+            QueryParameter::ExpiresBefore(now),
+        ]
+    } else {
+        // Get output ids of outputs that can be controlled by this address without further unlock constraints
+        vec![QueryParameter::Address(address)]
+    };
+    // the storage deposit return of an expired output no longer applies, as the return address
+    // unlocks it on its own
+    if !args.only_expired && args.storage_deposit_return == StorageDepositReturn::Exclude {
+        query_parameters.push(QueryParameter::HasStorageDepositReturn(false));
+    }
+    query_parameters
+}
+
+/// Prints the number of outputs to sweep per address, without fetching them.
+async fn print_output_counts(args: &Args, clients: &[Client], now: u32) -> Result<()> {
+    for (i, key) in args.key.keys.iter().enumerate() {
+        let client = &clients[i % clients.len()];
+        let secret_manager = args.key.key_format.secret_manager(key)?;
+
+        let address = addresses::first_address(client, i, &secret_manager).await?;
+        if !args.only_addresses.is_empty() && !args.only_addresses.contains(&address) {
+            continue;
+        }
+
+        let output_ids = client
+            .basic_output_ids(query_parameters(args, address, now))
+            .await?
+            .items;
+        status!(
+            args.json,
+            "{}: {} outputs",
+            address_label(&address, args.show_fingerprint),
+            output_ids.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the unlocked NFTs of an address, each with a new output transferring it to the recipient.
+async fn nft_outputs(
+    client: &Client,
+    address: Bech32Address,
+    recipient_address: Bech32Address,
+    now: u32,
+    token_supply: u64,
+) -> Result<Vec<NftTransfer>> {
+    let output_ids_response = client
+        .nft_output_ids([
+            QueryParameter::Address(address),
+            QueryParameter::HasStorageDepositReturn(false),
+        ])
+        .await?;
+
+    let mut outputs = client.get_outputs(&output_ids_response.items).await?;
+    sort_by_output_id(&mut outputs);
+    let mut nfts = Vec::new();
+    for output in outputs {
+        if output.metadata().is_spent() {
+            continue;
+        }
+        let Output::Nft(nft_output) = output.output() else {
+            continue;
+        };
+
+        let unavailable = nft_output.unlock_conditions().is_time_locked(now)
+            || nft_output.unlock_conditions().is_expired(now);
+        if unavailable {
+            continue;
+        }
+
+        // freshly minted NFTs have a null id that must be replaced by the one derived from the output id,
+        // and the sender feature is dropped since the original sender cannot be unlocked by us
+        let input_id = output.metadata().output_id();
+        let nft_id = nft_output.nft_id_non_null(input_id);
+        let output = NftOutputBuilder::from(nft_output)
+            .with_nft_id(nft_id)
+            .with_unlock_conditions([AddressUnlockCondition::new(recipient_address)])
+            .with_features(
+                nft_output
+                    .features()
+                    .iter()
+                    .filter(|feature| !feature.is_sender())
+                    .cloned(),
+            )
+            .finish_output(token_supply)?;
+        nfts.push(NftTransfer {
+            nft_id,
+            input: UtxoInput::from(*input_id),
+            output,
+        });
+    }
+
+    Ok(nfts)
+}
+
+/// Treatment of basic outputs with a storage deposit return unlock condition.
+///
+/// Unlocking such an output requires returning the deposit in the same transaction, so only the
+/// net amount can ever be swept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum StorageDepositReturn {
+    /// Ignore these outputs
+    Exclude,
+    /// Sweep the amount minus the return, sending the return back to its address
+    IncludeNet,
+}
+
+/// Order in which the keys are swept, once the funds of all keys are known.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SweepOrder {
+    /// The order the keys were given in
+    Input,
+    /// Largest swept amount first
+    BalanceDesc,
+    /// Smallest swept amount first
+    BalanceAsc,
+}
+
+/// Returns the amount of the output without the storage deposit that must be returned, which no
+/// longer applies once the output is expired.
+fn net_amount(output: &Output, now: u32) -> u64 {
+    let returned = output
+        .unlock_conditions()
+        .filter(|unlock_conditions| !unlock_conditions.is_expired(now))
+        .and_then(|unlock_conditions| unlock_conditions.storage_deposit_return())
+        .map_or(0, |sdr| sdr.amount());
+    output.amount() - returned
+}
+
+/// How an unspent output of an address is treated by the sweep.
+#[derive(Debug, PartialEq, Eq)]
+enum Eligibility {
+    /// Time-locked until the timestamp, never swept even if also expired
+    Locked(u32),
+    /// Can be unlocked by the address without further constraints
+    Sweepable,
+    /// Cannot or should not be swept by the address
+    Ignored,
+}
+
+/// Classifies an output at the node's time `now`.
+///
+/// An output is unlocked once `now` reaches its timelock and expired once `now` reaches its
+/// expiration, exactly as the protocol checks them. Unlocked outputs are sweepable unless expired,
+/// as they then belong to the return address. With `only_expired`, only expired outputs returning
+/// to `address` are sweepable instead.
+fn eligibility(
+    output: &Output,
+    address: &Bech32Address,
+    now: u32,
+    only_expired: bool,
+) -> Eligibility {
+    let Some(unlock_conditions) = output.unlock_conditions() else {
+        return Eligibility::Ignored;
+    };
+    if let Some(timelock) = unlock_conditions
+        .timelock()
+        .filter(|_| unlock_conditions.is_time_locked(now))
+    {
+        return Eligibility::Locked(timelock.timestamp());
+    }
+
+    let expired = unlock_conditions.is_expired(now);
+    let sweepable = if only_expired {
+        // the expired output must return to us, otherwise we cannot unlock it
+        let returns_to_us = unlock_conditions
+            .expiration()
+            .is_some_and(|e| e.return_address() == address.inner());
+        expired && returns_to_us
+    } else {
+        !expired
+    };
+
+    if sweepable {
+        Eligibility::Sweepable
+    } else {
+        Eligibility::Ignored
+    }
+}
+
+/// Splits the amount into equal parts, adding the remainder of the division to the first.
+fn split_amount(amount: u64, parts: u16) -> Vec<u64> {
+    let parts = u64::from(parts);
+    let mut amounts = vec![amount / parts; parts as usize];
+    amounts[0] += amount % parts;
+    amounts
+}
+
+/// Builds the basic output sending the amount to the recipient, optionally returning to the source
+/// address once expired and time-locked until the given timestamp.
+fn sweep_output(
+    amount: u64,
+    recipient: Bech32Address,
+    source: Bech32Address,
+    expires_at: Option<u32>,
+    unlocks_at: Option<u32>,
+    return_amount: Option<u64>,
+    token_supply: u64,
+) -> Result<Output> {
+    let mut builder = BasicOutputBuilder::new_with_amount(amount)
+        .add_unlock_condition(AddressUnlockCondition::new(recipient));
+    if let Some(timestamp) = expires_at {
+        builder = builder.add_unlock_condition(ExpirationUnlockCondition::new(source, timestamp)?);
+    }
+    if let Some(timestamp) = unlocks_at {
+        builder = builder.add_unlock_condition(TimelockUnlockCondition::new(timestamp)?);
+    }
+    if let Some(amount) = return_amount {
+        builder = builder.add_unlock_condition(StorageDepositReturnUnlockCondition::new(
+            source,
+            amount,
+            token_supply,
+        )?);
+    }
+
+    Ok(builder.finish_output(token_supply)?)
+}
+
+/// Lets the user pick the candidates to sweep, keeping all of them if there is no terminal.
+fn select_candidates(
+    candidates: Vec<Candidate>,
+    show_fingerprint: bool,
+    format: AmountFormat,
+) -> Result<Vec<Candidate>> {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        eprintln!("Warning: no terminal for interactive selection, sweeping all addresses");
+        return Ok(candidates);
+    }
+    if candidates.is_empty() {
+        return Ok(candidates);
+    }
+
+    let items: Vec<_> = candidates
+        .iter()
+        .map(|c| {
+            format!(
+                "{} ({})",
+                address_label(&c.address, show_fingerprint),
+                format.format(c.amount)
+            )
+        })
+        .collect();
+    let selection = MultiSelect::new()
+        .with_prompt("Select the addresses to sweep")
+        .items(&items)
+        .defaults(&vec![true; items.len()])
+        .interact()?;
+
+    Ok(candidates
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| selection.contains(i))
+        .map(|(_, c)| c)
+        .collect())
+}
+
+/// A completed sweep of a single key.
+#[derive(Debug, Serialize, Deserialize)]
+struct Sweep {
+    address: Bech32Address,
+    amount: u64,
+    /// Minimum amount that applied to the address, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_amount: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    nft_ids: Vec<NftId>,
+    block_id: BlockId,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    network: NetworkInfo,
+    recipient_address: Bech32Address,
+    /// Whether only expired outputs returning to the keys were swept, with `--only-expired`
+    only_expired: bool,
+    num_keys: usize,
+    /// Keys that were skipped, swept or failed so far
+    keys_processed: usize,
+    keys_with_funds: usize,
+    num_sweeps: usize,
+    interrupted: bool,
+    /// Whether the run was stopped by `--deadline`
+    deadline_reached: bool,
+    total_amount: u64,
+    sweeps: Vec<Sweep>,
+    /// Addresses whose sweep failed with `--continue-on-error`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed: Vec<Bech32Address>,
+}
+
+/// Formats an address, optionally followed by the first bytes of its hash as a fingerprint.
+fn address_label(address: &Bech32Address, show_fingerprint: bool) -> String {
+    if !show_fingerprint {
+        return address.to_string();
+    }
+
+    let hash = Blake2b256::digest(address.to_string());
+    let fingerprint: String = hash[..3].iter().map(|b| format!("{b:02x}")).collect();
+    format!("{address} [{fingerprint}]")
+}
+
+/// Reads bech32 addresses from a file, one per line, ignoring empty lines and `#` comments.
+fn read_addresses(path: &Path) -> Result<Vec<Bech32Address>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read '{}'", path.display()))?;
+
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.parse()
+                .with_context(|| format!("invalid address '{}' in '{}'", line, path.display()))
+        })
+        .collect()
+}
+
+/// Reads the minimum amounts per address, one `<address> <amount>` per line, ignoring `#` comments.
+fn read_min_amounts(path: &Path) -> Result<HashMap<Bech32Address, Amount>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read '{}'", path.display()))?;
+
+    let mut min_amounts = HashMap::new();
+    for line in content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+    {
+        let (address, amount) = line
+            .split_once(char::is_whitespace)
+            .with_context(|| format!("missing amount in '{}' in '{}'", line, path.display()))?;
+        let address: Bech32Address = address
+            .parse()
+            .with_context(|| format!("invalid address '{}' in '{}'", address, path.display()))?;
+        let amount = amount
+            .parse()
+            .with_context(|| format!("invalid amount in '{}' in '{}'", line, path.display()))?;
+        ensure!(
+            min_amounts.insert(address, amount).is_none(),
+            "duplicate address {address} in '{}'",
+            path.display()
+        );
+    }
+
+    Ok(min_amounts)
+}
+
+/// Parses a duration like `90s`, `30m`, `12h`, `7d` or `2w`, plain numbers are seconds.
+fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("invalid number in duration '{s}'"))?;
+    let factor = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => bail!("invalid unit '{unit}' in duration '{s}', use s, m, h, d or w"),
+    };
+    let secs = number
+        .checked_mul(factor)
+        .with_context(|| format!("duration '{s}' is too long"))?;
+    ensure!(secs > 0, "duration must not be zero");
+
+    Ok(Duration::from_secs(secs))
+}
+
+/// Checks that the tag fits into a tagged data payload.
+fn parse_tag(s: &str) -> Result<String> {
+    let max = *TaggedDataPayload::TAG_LENGTH_RANGE.end();
+    ensure!(
+        s.len() <= max.into(),
+        "tag is {} bytes long, at most {max} are allowed",
+        s.len()
+    );
+
+    Ok(s.to_string())
+}
+
+/// Parses the data of a tagged data payload, hex if prefixed with `0x` and UTF-8 otherwise.
+fn parse_data(s: &str) -> Result<Vec<u8>> {
+    let data = match s.strip_prefix("0x") {
+        Some(hex) => {
+            ensure!(
+                hex.len() % 2 == 0 && hex.bytes().all(|b| b.is_ascii_hexdigit()),
+                "invalid hex data '{s}'"
+            );
+            (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<_, _>>()?
+        }
+        None => s.as_bytes().to_vec(),
+    };
+    let max = *TaggedDataPayload::DATA_LENGTH_RANGE.end();
+    ensure!(
+        data.len() <= max as usize,
+        "data is {} bytes long, at most {max} are allowed",
+        data.len()
+    );
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use iota_sdk::types::block::{address::Ed25519Address, payload::transaction::TransactionId};
+
+    use super::*;
+
+    const NOW: u32 = 1_700_000_000;
+
+    /// Returns a test address, `address(1)` is the one being swept.
+    fn address(byte: u8) -> Bech32Address {
+        Bech32Address::new(
+            Hrp::from_str_unchecked("rms"),
+            Ed25519Address::new([byte; 32]),
+        )
+    }
+
+    /// Builds a basic output owned by `owner`, optionally time-locked and expiring to our address.
+    fn output(owner: Bech32Address, timelock: Option<u32>, expiration: Option<u32>) -> Output {
+        let mut builder = BasicOutputBuilder::new_with_amount(1_000_000)
+            .add_unlock_condition(AddressUnlockCondition::new(owner));
+        if let Some(timestamp) = timelock {
+            builder =
+                builder.add_unlock_condition(TimelockUnlockCondition::new(timestamp).unwrap());
+        }
+        if let Some(timestamp) = expiration {
+            builder = builder.add_unlock_condition(
+                ExpirationUnlockCondition::new(address(1), timestamp).unwrap(),
+            );
+        }
+        builder.finish_output(1_813_620_509_061_365).unwrap()
+    }
+
+    fn classify(output: &Output, only_expired: bool) -> Eligibility {
+        eligibility(output, &address(1), NOW, only_expired)
+    }
+
+    #[test]
+    fn nft_inputs_are_only_added_to_explicit_inputs() {
+        let nfts: Vec<_> = [7, 8]
+            .into_iter()
+            .map(|transaction| NftTransfer {
+                nft_id: NftId::new([transaction; 32]),
+                input: UtxoInput::new(TransactionId::new([transaction; 32]), 0).unwrap(),
+                output: output(address(3), None, None),
+            })
+            .collect();
+
+        assert!(with_nft_inputs(Vec::new(), &nfts).is_empty());
+
+        let input = UtxoInput::new(TransactionId::new([1; 32]), 2).unwrap();
+        assert_eq!(
+            with_nft_inputs(vec![input], &nfts),
+            [input, nfts[0].input, nfts[1].input]
+        );
+    }
+
+    #[test]
+    fn locked_output_is_locked() {
+        let output = output(address(1), Some(NOW + 1), None);
+        assert_eq!(classify(&output, false), Eligibility::Locked(NOW + 1));
+        assert_eq!(classify(&output, true), Eligibility::Locked(NOW + 1));
+    }
+
+    #[test]
+    fn expired_output_returning_to_us_is_only_swept_with_only_expired() {
+        let output = output(address(2), None, Some(NOW - 1));
+        assert_eq!(classify(&output, false), Eligibility::Ignored);
+        assert_eq!(classify(&output, true), Eligibility::Sweepable);
+    }
+
+    #[test]
+    fn plain_output_is_not_swept_with_only_expired() {
+        let output = output(address(1), None, None);
+        assert_eq!(classify(&output, false), Eligibility::Sweepable);
+        assert_eq!(classify(&output, true), Eligibility::Ignored);
+    }
+
+    #[test]
+    fn output_unlocks_and_expires_when_now_reaches_the_timestamp() {
+        let unlocked = output(address(1), Some(NOW), None);
+        assert_eq!(classify(&unlocked, false), Eligibility::Sweepable);
+
+        let expired = output(address(2), None, Some(NOW));
+        assert_eq!(classify(&expired, false), Eligibility::Ignored);
+        assert_eq!(classify(&expired, true), Eligibility::Sweepable);
+    }
+
+    #[test]
+    fn locked_and_expired_output_is_locked() {
+        let output = output(address(2), Some(NOW + 1), Some(NOW - 1));
+        assert_eq!(classify(&output, false), Eligibility::Locked(NOW + 1));
+        assert_eq!(classify(&output, true), Eligibility::Locked(NOW + 1));
+    }
+}
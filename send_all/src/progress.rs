@@ -0,0 +1,124 @@
+//! Records of the processed keys, printed as JSON lines to stderr with `--progress-json` and
+//! appended to the file of `--per-key-out`.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use iota_sdk::types::block::{address::Bech32Address, BlockId};
+use serde::Serialize;
+
+use crate::{Candidate, Sweep};
+
+/// Outcome of processing a key.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Nothing is sent from the key, e.g. because it has no funds
+    Skipped,
+    /// The funds of the key were swept
+    Swept,
+    /// Sweeping the funds of the key failed, it is retried with `--retry-failed`
+    Failed,
+}
+
+/// Record of a processed key, amounts are in glow.
+#[derive(Debug, Serialize)]
+pub struct KeyRecord {
+    key_index: usize,
+    address: Bech32Address,
+    status: Status,
+    /// Amount found at the address
+    found: u64,
+    /// Amount that was swept, or failed to be swept
+    amount: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_id: Option<BlockId>,
+    /// Time spent looking up the outputs of the key and sweeping them
+    duration_ms: u128,
+}
+
+impl KeyRecord {
+    pub fn skipped(
+        key_index: usize,
+        address: Bech32Address,
+        found: u64,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            key_index,
+            address,
+            status: Status::Skipped,
+            found,
+            amount: 0,
+            block_id: None,
+            duration_ms: duration.as_millis(),
+        }
+    }
+
+    pub fn swept(candidate: &Candidate, sweep: &Sweep, duration: Duration) -> Self {
+        Self {
+            key_index: candidate.key_index,
+            address: candidate.address,
+            status: Status::Swept,
+            found: candidate.found,
+            amount: sweep.amount,
+            block_id: Some(sweep.block_id),
+            duration_ms: (candidate.lookup_time + duration).as_millis(),
+        }
+    }
+
+    pub fn failed(candidate: &Candidate, duration: Duration) -> Self {
+        Self {
+            key_index: candidate.key_index,
+            address: candidate.address,
+            status: Status::Failed,
+            found: candidate.found,
+            amount: candidate.amount,
+            block_id: None,
+            duration_ms: (candidate.lookup_time + duration).as_millis(),
+        }
+    }
+}
+
+/// Destinations of the records.
+pub struct Progress {
+    json: bool,
+    per_key_out: Option<File>,
+}
+
+impl Progress {
+    /// Creates the destinations, appending to the per-key file if it already exists.
+    pub fn new(json: bool, per_key_out: Option<&Path>) -> Result<Self> {
+        let per_key_out = per_key_out
+            .map(|path| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("failed to open '{}'", path.display()))
+            })
+            .transpose()?;
+
+        Ok(Self { json, per_key_out })
+    }
+
+    /// Prints the record to stderr, if enabled, and appends it to the per-key file, if any.
+    pub fn record(&mut self, record: &KeyRecord) -> Result<()> {
+        let line = serde_json::to_string(record)?;
+        if self.json {
+            eprintln!("{line}");
+        }
+        if let Some(file) = &mut self.per_key_out {
+            // written as soon as the key completes, so that the file is complete up to a crash
+            writeln!(file, "{line}")?;
+            file.sync_all()?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+//! Detached signatures of the summary file, so that third parties can verify who produced it.
+//!
+//! The Ed25519 signature covers the exact bytes of the summary file. It is written next to it as
+//! `<summary file>.sig`, a JSON object with the hex encoded `public_key` and `signature`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use iota_sdk::{
+    client::{
+        constants::IOTA_COIN_TYPE,
+        secret::{private_key::PrivateKeySecretManager, SecretManage},
+    },
+    crypto::keys::bip44::Bip44,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Receipt {
+    public_key: String,
+    signature: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Signs the summary file with the base58 encoded private key, returning the signature file.
+pub async fn sign(summary_path: &Path, key: &str) -> Result<PathBuf> {
+    let message = std::fs::read(summary_path)
+        .with_context(|| format!("failed to read '{}'", summary_path.display()))?;
+    let secret_manager = PrivateKeySecretManager::try_from_b58(key)?;
+    // a single private key does not depend on the derivation path
+    let signature = secret_manager
+        .sign_ed25519(&message, Bip44::new(IOTA_COIN_TYPE))
+        .await?;
+    let receipt = Receipt {
+        public_key: hex(&signature.public_key().to_bytes()),
+        signature: hex(&signature.signature().to_bytes()),
+    };
+
+    let mut path = summary_path.as_os_str().to_owned();
+    path.push(".sig");
+    let path = PathBuf::from(path);
+    std::fs::write(&path, serde_json::to_string(&receipt)?)
+        .with_context(|| format!("failed to write '{}'", path.display()))?;
+
+    Ok(path)
+}
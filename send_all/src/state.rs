@@ -0,0 +1,66 @@
+//! Append-only state file recording the completed sweeps, to resume interrupted runs, and the
+//! summary file reporting the progress.
+
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use iota_sdk::types::block::address::Bech32Address;
+
+use crate::{Summary, Sweep};
+
+/// Returns the addresses of all sweeps recorded in the state file.
+pub fn read_completed(path: &Path) -> Result<HashSet<Bech32Address>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read '{}'", path.display())),
+    };
+
+    let mut completed = HashSet::new();
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        // a crash while appending can leave an incomplete last line
+        match serde_json::from_str::<Sweep>(line) {
+            Ok(sweep) => {
+                completed.insert(sweep.address);
+            }
+            Err(e) => eprintln!(
+                "Warning: ignoring invalid line in '{}': {e}",
+                path.display()
+            ),
+        }
+    }
+
+    Ok(completed)
+}
+
+/// Appends a completed sweep to the state file.
+pub fn append_completed(path: &Path, sweep: &Sweep) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open '{}'", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(sweep)?)?;
+    file.sync_all()?;
+
+    Ok(())
+}
+
+/// Atomically replaces the summary file, so that it never contains a partially written summary.
+pub fn write_summary(path: &Path, summary: &Summary) -> Result<()> {
+    let mut tmp_name = path.file_name().context("invalid summary path")?.to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file = std::fs::File::create(&tmp_path)
+        .with_context(|| format!("failed to create '{}'", tmp_path.display()))?;
+    serde_json::to_writer(&mut file, summary)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("failed to write '{}'", path.display()))
+}
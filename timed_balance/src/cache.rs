@@ -0,0 +1,103 @@
+//! Opt-in on-disk cache of the fetched outputs, to speed up repeated runs against the same state.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use iota_sdk::types::{
+    block::{
+        address::Bech32Address,
+        output::{dto::OutputDto, Output, OutputMetadata, OutputWithMetadata},
+    },
+    TryFromDto,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::OutputType;
+
+#[derive(Serialize, Deserialize)]
+struct CachedOutput {
+    output: OutputDto,
+    metadata: OutputMetadata,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    outputs: Vec<CachedOutput>,
+}
+
+/// Outputs of each address and output type, stored in the temporary directory.
+pub struct OutputCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl OutputCache {
+    /// Creates a cache whose entries are valid for the given time, outputs with a storage deposit
+    /// return are only fetched on request and thus cached separately.
+    pub fn new(ttl: Duration, with_storage_deposit_returns: bool) -> Self {
+        let dir = if with_storage_deposit_returns {
+            "timed_balance-outputs-sdr"
+        } else {
+            "timed_balance-outputs"
+        };
+        Self {
+            dir: std::env::temp_dir().join(dir),
+            ttl,
+        }
+    }
+
+    fn path(&self, address: &Bech32Address, output_type: OutputType) -> PathBuf {
+        self.dir.join(format!("{address}-{output_type}.json"))
+    }
+
+    /// Returns the cached outputs, if they were stored within the TTL.
+    pub fn load(
+        &self,
+        address: &Bech32Address,
+        output_type: OutputType,
+    ) -> Option<Vec<OutputWithMetadata>> {
+        let path = self.path(address, output_type);
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if SystemTime::now().duration_since(modified).ok()? > self.ttl {
+            return None;
+        }
+
+        let content = std::fs::read_to_string(&path).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+        entry
+            .outputs
+            .into_iter()
+            .map(|cached| {
+                let output = Output::try_from_dto(cached.output).ok()?;
+                Some(OutputWithMetadata::new(output, cached.metadata))
+            })
+            .collect()
+    }
+
+    /// Stores the outputs, replacing any previous entry.
+    pub fn store(
+        &self,
+        address: &Bech32Address,
+        output_type: OutputType,
+        outputs: &[OutputWithMetadata],
+    ) -> Result<()> {
+        let entry = CacheEntry {
+            outputs: outputs
+                .iter()
+                .map(|output| CachedOutput {
+                    output: output.output().into(),
+                    metadata: *output.metadata(),
+                })
+                .collect(),
+        };
+
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create '{}'", self.dir.display()))?;
+        let path = self.path(address, output_type);
+        std::fs::write(&path, serde_json::to_string(&entry)?)
+            .with_context(|| format!("failed to write '{}'", path.display()))
+    }
+}
@@ -0,0 +1,53 @@
+//! Export of the future unlocks as an iCalendar file.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+
+use common::format::AmountFormat;
+
+/// Formats a timestamp as an iCalendar UTC date-time.
+fn ical_time(ts: u32) -> Result<String> {
+    let time = NaiveDateTime::from_timestamp_opt(ts.into(), 0).context("invalid timestamp")?;
+    Ok(time.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+/// Writes one event per future unlock, with the unlocked amount in the title.
+pub fn write(
+    path: &Path,
+    balances: &BTreeMap<u32, u64>,
+    now: u32,
+    format: AmountFormat,
+) -> Result<()> {
+    let mut ics = String::new();
+    ics.push_str("BEGIN:VCALENDAR\r\n");
+    ics.push_str("VERSION:2.0\r\n");
+    ics.push_str("PRODID:-//iota-playground//timed_balance//EN\r\n");
+
+    let stamp = ical_time(now)?;
+    let mut cumulative = 0;
+    for (&ts, &amount) in balances {
+        cumulative += amount;
+        if ts <= now {
+            continue;
+        }
+
+        let start = ical_time(ts)?;
+        ics.push_str("BEGIN:VEVENT\r\n");
+        write!(ics, "UID:{ts}-{amount}@timed_balance\r\n")?;
+        write!(ics, "DTSTAMP:{stamp}\r\n")?;
+        write!(ics, "DTSTART:{start}\r\n")?;
+        write!(ics, "SUMMARY:Unlock of {}\r\n", format.format(amount))?;
+        write!(
+            ics,
+            "DESCRIPTION:Unlocks {}\\, bringing the unlocked total to {}\r\n",
+            format.format(amount),
+            format.format(cumulative)
+        )?;
+        ics.push_str("END:VEVENT\r\n");
+    }
+    ics.push_str("END:VCALENDAR\r\n");
+
+    std::fs::write(path, ics).with_context(|| format!("failed to write '{}'", path.display()))
+}
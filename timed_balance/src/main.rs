@@ -1,167 +1,1648 @@
-use anyhow::{Context, Result};
-use chrono::NaiveDateTime;
-use clap::Parser;
-use iota_sdk::client::{
-    api::GetAddressesOptions,
-    node_api::indexer::query_parameters::QueryParameter,
-    secret::{private_key::PrivateKeySecretManager, SecretManager},
-    Client,
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use chrono::{Datelike, NaiveDateTime};
+use clap::{ArgAction, Parser, ValueEnum};
+use iota_sdk::{
+    client::{node_api::indexer::query_parameters::QueryParameter, Client},
+    types::block::{
+        address::{AliasAddress, Bech32Address, Hrp},
+        output::{
+            feature::TagFeature, unlock_condition::AddressUnlockCondition, BasicOutputBuilder,
+            Output, OutputId, OutputMetadata, OutputWithMetadata, RentStructure,
+        },
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::IsTerminal,
+    path::PathBuf,
+    time::Duration,
 };
-use serde::Deserialize;
-use std::collections::BTreeMap;
 use tabled::{
-    settings::{Alignment, Style},
+    settings::{locator::ByColumnName, object::Rows, Alignment, Color, Disable, Modify, Style},
     Table, Tabled,
 };
 
+use common::{
+    addresses,
+    amount::Amount,
+    format::{AmountFormat, DisplayUnit, Rounding},
+    keys::KeyArgs,
+    network::Network,
+    node::{mask_credentials, normalize_node_url, NodeArgs},
+    outputs::sort_by_output_id,
+    parse_datetime,
+    runtime::{self, RuntimeArgs},
+    webhook::WebhookArgs,
+};
+
+use self::{
+    cache::OutputCache,
+    price::{get_price, PriceSource, RatesFile},
+    summary::{digest, print_diff, Summary},
+};
+
+mod cache;
+mod ical;
+mod price;
+mod state;
+mod summary;
+
 /// Simple program to display the timelocked balances of a list of private keys
 #[derive(Parser, Debug)]
-#[command(about, long_about = None)]
+#[command(
+    about,
+    long_about = None,
+    mut_arg("node_url", |arg| {
+        arg.required_unless_present_any([
+            "keyring_entry",
+            "derive",
+            "validate_keys_only",
+            "network_node",
+        ])
+    })
+)]
 struct Args {
-    /// Node URL to issue transactions with
-    #[arg(short, long, env = "NODE_URL")]
-    node_url: String,
+    #[command(flatten)]
+    node: NodeArgs,
+
+    /// Label of the node shown in the report instead of its URL
+    #[arg(long)]
+    node_label: Option<String>,
+
+    /// Node of a network to count the keys on, as `<network>=<url>`, e.g.
+    /// `shimmer=https://api.shimmer.network`, instead of `--node-url` and `--network`
+    ///
+    /// Repeat it to report on several networks at once, or to use several nodes of a network. Each
+    /// network is counted with the addresses of the keys in its own HRP, as private keys derive the
+    /// same address regardless of the coin type, and valued at the price of its own token, so the
+    /// report has a network column and a total per network.
+    #[arg(
+        long,
+        value_name = "NETWORK=URL",
+        value_parser = parse_network_node,
+        conflicts_with_all = [
+            "network", "assert_network", "node_label", "print_addresses", "output_id",
+            "count_only", "summarize_by_month", "group_dust_threshold", "show_contributors",
+            "show_deposit_outputs", "show_percent", "json", "save", "baseline", "webhook",
+            "ical_out", "rates_file", "price_url", "assume_price",
+        ]
+    )]
+    network_node: Vec<NetworkNode>,
 
     /// Currency to display the value in
     #[arg(short, long, default_value = "eur")]
     currency: String,
 
-    /// Base58 encoded private keys
-    #[arg(long, value_delimiter = ',', env = "PRIVATE_KEYS")]
-    keys: Vec<String>,
+    /// Display values with the currency symbol instead of the code, optionally with a custom symbol
+    #[arg(long, num_args = 0..=1, value_name = "SYMBOL")]
+    currency_symbol: Option<Option<String>>,
+
+    #[command(flatten)]
+    key: KeyArgs,
+
+    /// Print the address derived from each key and exit, to compare the derivation of both tools
+    #[arg(long)]
+    print_addresses: bool,
+
+    /// Print the address derived from each key for `--hrp`, one per line, and exit without
+    /// contacting a node
+    #[arg(long, requires = "hrp", conflicts_with = "print_addresses")]
+    derive: bool,
+
+    /// Check that every key parses and derives an address for `--hrp`, print the number of valid
+    /// keys and exit without contacting a node
+    #[arg(long, requires = "hrp", conflicts_with_all = ["print_addresses", "derive"])]
+    validate_keys_only: bool,
+
+    /// Human readable part of the derived addresses, e.g. `iota` or `smr`
+    #[arg(long)]
+    hrp: Option<Hrp>,
+
+    /// Only consider the first n outputs of each address and output type
+    #[arg(long)]
+    max_outputs: Option<usize>,
+
+    /// Only process keys deriving to one of these addresses
+    #[arg(long, value_delimiter = ',')]
+    only_addresses: Vec<Bech32Address>,
+
+    /// Output types to include in the balances
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "basic")]
+    output_types: Vec<OutputType>,
+
+    /// How basic and NFT outputs with a storage deposit return are counted
+    #[arg(long, value_enum, default_value_t = StorageDepositReturn::Exclude)]
+    storage_deposit_return: StorageDepositReturn,
+
+    /// Whether basic and NFT outputs must have an expiration unlock condition
+    #[arg(long, value_name = "BOOL", default_value_t = false, action = ArgAction::Set)]
+    has_expiration: bool,
+
+    /// Whether basic and NFT outputs must have a timelock unlock condition, both if not given
+    #[arg(long, value_name = "BOOL")]
+    has_timelock: Option<bool>,
+
+    /// Whether basic and NFT outputs must have a storage deposit return unlock condition, instead
+    /// of the filter implied by `--storage-deposit-return`
+    #[arg(long, value_name = "BOOL", conflicts_with = "storage_deposit_return")]
+    has_storage_deposit_return: Option<bool>,
+
+    /// Bucket of the outputs without a timelock
+    #[arg(long, value_enum, default_value_t = NoTimelockBucket::Booked)]
+    no_timelock_bucket: NoTimelockBucket,
+
+    /// Only count basic and NFT outputs with this tag feature, hex if prefixed with `0x`, UTF-8
+    /// otherwise
+    #[arg(long, value_parser = parse_tag)]
+    tag: Option<String>,
+
+    /// Show each amount as a percentage of the total
+    #[arg(long)]
+    show_percent: bool,
+
+    /// Omit the cumulative columns from the table and the JSON summary
+    #[arg(long)]
+    no_cumulative: bool,
+
+    /// Show whether each unlock is spendable at the node's current time
+    #[arg(long)]
+    spendable_now: bool,
+
+    /// Show the number of outputs of each unlock holding exactly the minimum storage deposit, which
+    /// are likely locked as deposit rather than freely available, and a note with their total
+    #[arg(long)]
+    show_deposit_outputs: bool,
+
+    /// Show the raw unix timestamp of each unlock next to the formatted time
+    #[arg(long)]
+    show_raw_timestamps: bool,
+
+    /// Print the ledger metadata of every fetched output to stderr, to debug why it is or is not
+    /// counted
+    #[arg(long)]
+    output_metadata: bool,
+
+    /// Abort on outputs with unlock conditions whose amount is not modeled exactly, instead of
+    /// counting them anyway
+    #[arg(long)]
+    strict_balance: bool,
+
+    /// Show the addresses contributing to each unlock
+    #[arg(long)]
+    show_contributors: bool,
+
+    /// List all contributing addresses instead of their count for unlocks with many of them
+    #[arg(long, requires = "show_contributors")]
+    verbose: bool,
+
+    /// Collapse the unlocks of less than this amount into a single row, e.g. `1.5IOTA`,
+    /// `500kglow` or `1000` glow
+    #[arg(long)]
+    group_dust_threshold: Option<Amount>,
+
+    /// Only print the total of all keys and its value, without the unlocks, for a quick check of
+    /// large wallets
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "summarize_by_month", "show_contributors", "group_dust_threshold", "save", "baseline",
+            "ical_out", "webhook"
+        ]
+    )]
+    total_only: bool,
+
+    /// Print a JSON summary instead of the table
+    #[arg(long)]
+    json: bool,
+
+    /// Indent the JSON output for reading instead of printing it on a single line
+    #[arg(long, requires = "json")]
+    json_pretty: bool,
+
+    /// Save the JSON summary to a file, to be used as a baseline later
+    #[arg(long)]
+    save: Option<PathBuf>,
+
+    /// Export the future unlocks as iCalendar events to this file
+    #[arg(long)]
+    ical_out: Option<PathBuf>,
+
+    /// Print the changes compared to a previously saved JSON summary
+    #[arg(long, conflicts_with = "json")]
+    baseline: Option<PathBuf>,
+
+    #[command(flatten)]
+    webhook: WebhookArgs,
+
+    /// Only inspect a single output and show how it is counted
+    #[arg(long)]
+    output_id: Option<OutputId>,
+
+    /// Only print the number of outputs per address, without fetching the outputs themselves
+    ///
+    /// Foundries can only be found by fetching the aliases controlling them.
+    #[arg(long, conflicts_with = "output_id")]
+    count_only: bool,
+
+    /// Reuse the outputs fetched by previous runs within this many seconds, for repeated runs
+    /// against slowly changing state
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        conflicts_with_all = [
+            "has_expiration",
+            "has_timelock",
+            "has_storage_deposit_return",
+            "tag",
+            "max_outputs",
+        ]
+    )]
+    cache_outputs: Option<u64>,
+
+    /// File recording the balances of the processed addresses, to resume an interrupted run
+    ///
+    /// The recorded balances of an address are used instead of fetching its outputs again, so the
+    /// file must only be reused with the same options.
+    #[arg(long, value_name = "PATH")]
+    resume_state: Option<PathBuf>,
+
+    /// Fetch and count the outputs in batches, to bound the memory used for huge addresses
+    #[arg(long, conflicts_with = "cache_outputs")]
+    stream: bool,
+
+    /// Sources to get the price from, each is tried in order until one succeeds
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "coingecko")]
+    price_source: Vec<PriceSource>,
+
+    /// URL of the custom price source responding with the price as a plain number, `{currency}` is
+    /// replaced by the currency
+    #[arg(long)]
+    price_url: Option<String>,
+
+    /// JSON file with the prices to use instead of fetching them, for use without network access
+    ///
+    /// The file contains the date the prices were taken at and the prices by CoinGecko coin id
+    /// and currency, of which those of `--network` are used, e.g.
+    /// `{"date": "2024-01-31", "rates": {"iota": {"eur": 0.25, "usd": 0.27}, "shimmer": {"eur": 0.01}}}`.
+    #[arg(long, conflicts_with_all = ["price_source", "price_url"])]
+    rates_file: Option<PathBuf>,
+
+    /// Number of decimals of the CoinGecko price
+    #[arg(long, default_value_t = 18, value_parser = clap::value_parser!(u8).range(0..=18))]
+    price_precision: u8,
+
+    /// Retry the CoinGecko price request when rate limited, waiting at most this many seconds in total
+    #[arg(long, value_name = "SECONDS")]
+    retry_price_on_rate_limit: Option<u64>,
+
+    /// Print monthly totals with opening and closing cumulative amounts instead of each unlock
+    #[arg(long)]
+    summarize_by_month: bool,
+
+    /// Value future unlocks at this assumed price instead of the current one
+    #[arg(long)]
+    assume_price: Option<f64>,
+
+    /// Use the local clock instead of the node's time to determine what is unlocked
+    #[arg(long)]
+    use_local_time: bool,
+
+    /// Only count outputs booked by this UTC time, e.g. `2024-12-31 23:59:59`
+    ///
+    /// The indexer only lists unspent outputs, so outputs spent since then are missing and the
+    /// result is a lower bound of the balance at that time.
+    #[arg(long, value_parser = parse_datetime)]
+    as_of: Option<u32>,
+
+    /// Unit to display amounts in, values are always based on whole tokens
+    #[arg(long, value_enum, default_value_t = DisplayUnit::Iota)]
+    unit: DisplayUnit,
+
+    /// Network preset for the token symbol and the CoinGecko coin id
+    #[arg(long, value_enum, default_value_t = Network::Iota)]
+    network: Network,
+
+    /// Number of decimals to display amounts with, defaults to all decimals of the unit
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=18))]
+    decimals: Option<u32>,
+
+    /// How to round displayed amounts, this does not affect the amounts in the JSON output
+    #[arg(long, value_enum, default_value_t = Rounding::Nearest)]
+    rounding: Rounding,
+
+    /// Highlight unlocked and imminent large unlocks
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    #[command(flatten)]
+    runtime: RuntimeArgs,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    dotenvy::dotenv()?;
+impl Args {
+    /// Returns the format to display amounts with.
+    fn amount_format(&self) -> AmountFormat {
+        self.network_amount_format(self.network)
+    }
+
+    /// Returns the format to display amounts of the network with.
+    fn network_amount_format(&self, network: Network) -> AmountFormat {
+        AmountFormat {
+            network,
+            unit: self.unit,
+            decimals: self.decimals.unwrap_or(self.unit.exponent()),
+            rounding: self.rounding,
+        }
+    }
+
+    /// Returns the label to display values with, the currency symbol if requested and known.
+    fn currency_label(&self) -> String {
+        let code = self.currency.to_uppercase();
+        let symbol = match &self.currency_symbol {
+            Some(Some(symbol)) => Some(symbol.as_str()),
+            Some(None) => currency_symbol(&code),
+            None => None,
+        };
+        symbol.map_or(code, str::to_string)
+    }
+
+    /// Returns the indexer filters on the unlock conditions and the tag of basic and NFT outputs.
+    fn condition_filters(&self) -> Vec<QueryParameter> {
+        let mut filters = vec![QueryParameter::HasExpiration(self.has_expiration)];
+        if let Some(tag) = &self.tag {
+            filters.push(QueryParameter::Tag(tag.clone()));
+        }
+        if let Some(has_timelock) = self.has_timelock {
+            filters.push(QueryParameter::HasTimelock(has_timelock));
+        }
+        let has_storage_deposit_return = self
+            .has_storage_deposit_return
+            .or((self.storage_deposit_return == StorageDepositReturn::Exclude).then_some(false));
+        if let Some(has_storage_deposit_return) = has_storage_deposit_return {
+            filters.push(QueryParameter::HasStorageDepositReturn(
+                has_storage_deposit_return,
+            ));
+        }
+        filters
+    }
+
+    /// Returns the label of the nodes the report is generated from, their URLs without credentials
+    /// unless a label is given.
+    fn node_label(&self) -> Result<String> {
+        if let Some(label) = &self.node_label {
+            return Ok(label.clone());
+        }
+        let urls = self
+            .node
+            .node_url
+            .iter()
+            .map(|url| normalize_node_url(url).map(|url| mask_credentials(&url)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(urls.join(", "))
+    }
+}
+
+/// Node of a network given with `--network-node`.
+#[derive(Clone, Debug)]
+struct NetworkNode {
+    network: Network,
+    url: String,
+}
+
+/// Parses a `<network>=<url>` pair.
+fn parse_network_node(s: &str) -> Result<NetworkNode> {
+    let (network, url) = s
+        .split_once('=')
+        .with_context(|| format!("expected <network>=<url>, got '{s}'"))?;
+    let network = Network::from_str(network.trim(), true)
+        .map_err(|_| anyhow!("unknown network '{}'", network.trim()))?;
+    ensure!(!url.trim().is_empty(), "missing node URL in '{s}'");
+
+    Ok(NetworkNode {
+        network,
+        url: url.trim().to_string(),
+    })
+}
+
+/// Parses the tag of a tag feature, hex if prefixed with `0x` and UTF-8 otherwise, into the hex
+/// form the indexer is queried with.
+fn parse_tag(s: &str) -> Result<String> {
+    let hex = match s.strip_prefix("0x") {
+        Some(hex) => {
+            ensure!(
+                hex.len() % 2 == 0 && hex.bytes().all(|b| b.is_ascii_hexdigit()),
+                "invalid hex tag '{s}'"
+            );
+            hex.to_ascii_lowercase()
+        }
+        None => s.bytes().map(|b| format!("{b:02x}")).collect(),
+    };
+    let (min, max) = (
+        *TagFeature::LENGTH_RANGE.start(),
+        *TagFeature::LENGTH_RANGE.end(),
+    );
+    let len = hex.len() / 2;
+    ensure!(
+        (min.into()..=max.into()).contains(&len),
+        "tag is {len} bytes long, {min} to {max} are allowed"
+    );
+
+    Ok(format!("0x{hex}"))
+}
+
+/// Returns the symbol of well-known currencies.
+fn currency_symbol(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "EUR" => "€",
+        "USD" => "$",
+        "GBP" => "£",
+        "JPY" | "CNY" => "¥",
+        "INR" => "₹",
+        "KRW" => "₩",
+        "RUB" => "₽",
+        "BTC" => "₿",
+        "ETH" => "Ξ",
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OutputType {
+    /// Basic outputs owned by the address
+    Basic,
+    /// NFT outputs owned by the address
+    Nft,
+    /// Alias outputs with the address as state controller
+    Alias,
+    /// Foundry outputs controlled by aliases of the address
+    Foundry,
+}
+
+impl std::fmt::Display for OutputType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Treatment of outputs with a storage deposit return unlock condition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum StorageDepositReturn {
+    /// Do not count these outputs
+    Exclude,
+    /// Count the full amount of these outputs
+    IncludeGross,
+    /// Count the amount minus the deposit that must be returned
+    IncludeNet,
+}
+
+impl StorageDepositReturn {
+    /// Returns the amount of the output that is counted.
+    fn amount(self, output: &Output) -> u64 {
+        let returned = output
+            .unlock_conditions()
+            .and_then(|unlock_conditions| unlock_conditions.storage_deposit_return())
+            .map_or(0, |sdr| sdr.amount());
+        match self {
+            Self::Exclude | Self::IncludeGross => output.amount(),
+            Self::IncludeNet => output.amount() - returned,
+        }
+    }
+}
+
+/// Timestamp outputs without a timelock are counted at.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum NoTimelockBucket {
+    /// The time the output was booked
+    Booked,
+    /// The time of the report, the current time or `--as-of`
+    Now,
+    /// The unix epoch, to keep them apart from all unlocks
+    Zero,
+}
+
+impl NoTimelockBucket {
+    /// Returns the timestamp of the bucket for the report time, `None` for the booking time.
+    fn timestamp(self, now: u32) -> Option<u32> {
+        match self {
+            Self::Booked => None,
+            Self::Now => Some(now),
+            Self::Zero => Some(0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ColorChoice {
+    /// Use colors when printing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            Self::Auto => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    runtime::load_dotenv()?;
     let args = Args::parse();
+    args.runtime.build_runtime()?.block_on(run(args))
+}
+
+async fn run(mut args: Args) -> Result<()> {
+    args.key.load(&mut args.node)?;
+    args.output_types.sort();
+    args.output_types.dedup();
+    ensure!(
+        args.tag.is_none()
+            || args
+                .output_types
+                .iter()
+                .all(|output_type| matches!(output_type, OutputType::Basic | OutputType::Nft)),
+        "--tag only applies to basic and NFT outputs"
+    );
+    ensure!(
+        args.price_url.is_some() || !args.price_source.contains(&PriceSource::Custom),
+        "the custom price source requires --price-url"
+    );
+
+    if let (true, Some(hrp)) = (args.derive, args.hrp) {
+        return addresses::print_derived(&args.key.keys, args.key.key_format, hrp).await;
+    }
+    if let (true, Some(hrp)) = (args.validate_keys_only, args.hrp) {
+        return addresses::validate(&args.key.keys, args.key.key_format, hrp).await;
+    }
+
+    if !args.network_node.is_empty() {
+        let networks = collect_network_balances(&args).await?;
+        return print_network_balances(networks, &args);
+    }
+
+    // Create the node clients
+    let clients = args.node.build_clients().await?;
+    let client = &clients[0];
 
-    // Create a node client
-    let client = Client::builder()
-        .with_node(&args.node_url)?
-        .finish()
-        .await?;
-
-    let mut balances = BTreeMap::new();
-    for base58 in args.keys {
-        let secret_manager = SecretManager::from(PrivateKeySecretManager::try_from_b58(base58)?);
-
-        // Generate the first address
-        let mut addresses = secret_manager
-            .generate_ed25519_addresses(
-                GetAddressesOptions::from_client(&client)
-                    .await?
-                    .with_account_index(0)
-                    .with_range(0..1),
+    if args.print_addresses {
+        return addresses::print_addresses(&clients, &args.key.keys, args.key.key_format).await;
+    }
+    // the node's notion of now, which might differ from the local clock
+    let now = if args.use_local_time {
+        chrono::Utc::now().timestamp().try_into()?
+    } else {
+        client.get_time_checked().await?
+    };
+    if let Some(output_id) = args.output_id {
+        let no_timelock_timestamp = args.no_timelock_bucket.timestamp(now);
+        return print_output(
+            client,
+            output_id,
+            no_timelock_timestamp,
+            args.amount_format(),
+        )
+        .await;
+    }
+    if args.count_only {
+        return print_output_counts(&args, &clients).await;
+    }
+
+    if let Some(as_of) = args.as_of {
+        ensure!(as_of <= now, "--as-of must not be in the future");
+        eprintln!(
+            "Warning: outputs spent since the --as-of time are no longer listed by the indexer and are missing"
+        );
+    }
+
+    let protocol_parameters = client.get_protocol_parameters().await?;
+    let dust_threshold = match &args.group_dust_threshold {
+        Some(threshold) => Some(threshold.to_glow(&client.get_info().await?.node_info.base_token)?),
+        None => None,
+    };
+    let rates = args
+        .rates_file
+        .as_deref()
+        .map(RatesFile::load)
+        .transpose()?;
+
+    // fetch the price of the base token while querying the outputs
+    let price = async {
+        match &rates {
+            Some(rates) => Ok((rates.price(args.network.coin_id(), &args.currency)?, None)),
+            None => get_price(
+                &args.price_source,
+                args.network.coin_id(),
+                args.price_url.as_deref(),
+                &args.currency,
+                args.price_precision,
+                args.retry_price_on_rate_limit.map(Duration::from_secs),
             )
-            .await?;
-        let address = addresses.pop().unwrap();
-
-        // Get output ids of outputs that can be controlled by this address without further unlock constraints
-        let output_ids_response = client
-            .basic_output_ids([
-                QueryParameter::Address(address),
-                QueryParameter::HasExpiration(false),
-                QueryParameter::HasStorageDepositReturn(false),
-            ])
-            .await?;
-
-        let outputs_responses = client.get_outputs(&output_ids_response.items).await?;
-
-        for output in outputs_responses {
-            let metadata = output.metadata();
-            if metadata.is_spent() {
-                continue;
+            .await
+            .map(|(price, source)| (price, Some(source))),
+        }
+    };
+    let (balances, (price, price_source)) = tokio::try_join!(
+        collect_balances(
+            &args,
+            &clients,
+            *protocol_parameters.rent_structure(),
+            args.no_timelock_bucket.timestamp(args.as_of.unwrap_or(now))
+        ),
+        price
+    )?;
+    if args.total_only {
+        return print_total(balances.total, price, &args);
+    }
+    let Balances {
+        by_unlock_time: balances,
+        contributors,
+        by_output_type: output_type_totals,
+        dust_outputs,
+        dust_amount,
+        deposit_outputs,
+        ..
+    } = balances;
+
+    let summary = if args.json
+        || args.save.is_some()
+        || args.baseline.is_some()
+        || args.webhook.url.is_some()
+    {
+        let mut summary = Summary::new(
+            &balances,
+            price,
+            &args.currency,
+            &protocol_parameters,
+            !args.no_cumulative,
+        )?;
+        summary.node = Some(args.node_label()?);
+        if args.output_types.len() > 1 {
+            summary.output_type_totals = output_type_totals
+                .iter()
+                .map(|(output_type, &amount)| (output_type.to_string(), amount))
+                .collect();
+        }
+        Some(summary)
+    } else {
+        None
+    };
+
+    let digest = digest(&balances);
+    if let Some(path) = &args.ical_out {
+        ical::write(path, &balances, now, args.amount_format())?;
+    }
+
+    // print the balances
+    match &summary {
+        Some(summary) if args.json => {
+            let json = if args.json_pretty {
+                serde_json::to_string_pretty(summary)?
+            } else {
+                serde_json::to_string(summary)?
+            };
+            println!("{json}");
+        }
+        _ if args.summarize_by_month => print_monthly(balances, price, &args)?,
+        _ => print_balances(
+            balances,
+            &contributors,
+            &deposit_outputs,
+            price,
+            now,
+            dust_threshold,
+            &args,
+        )?,
+    }
+    if !args.json && args.output_types.len() > 1 {
+        print_output_type_totals(output_type_totals, price, &args);
+    }
+    if !args.json && dust_outputs > 0 {
+        println!(
+            "{dust_outputs} dust outputs holding {} total",
+            args.amount_format().format(dust_amount)
+        );
+    }
+    let num_deposit_outputs: usize = deposit_outputs.values().sum();
+    if !args.json && args.show_deposit_outputs && num_deposit_outputs > 0 {
+        println!(
+            "Note: {num_deposit_outputs} outputs hold exactly the minimum storage deposit, they are likely locked as deposit rather than freely available"
+        );
+    }
+    if !args.json {
+        let currency = args.currency_label();
+        match (&rates, price_source) {
+            (Some(rates), _) => {
+                println!(
+                    "Price of {price} {currency} from the rates of {}",
+                    rates.date
+                )
+            }
+            (None, Some(source)) if args.price_source.len() > 1 => {
+                println!("Price of {price} {currency} from {source}")
+            }
+            _ => {}
+        }
+        println!("Node: {}", args.node_label()?);
+        println!("Digest: {digest}");
+    }
+
+    if let Some(summary) = summary {
+        if let Some(path) = &args.baseline {
+            let baseline = Summary::load(path)?;
+            print_diff(&baseline, &summary, args.amount_format());
+        }
+        if let Some(path) = &args.save {
+            summary.save(path)?;
+        }
+        args.webhook.notify(&summary).await;
+    }
+
+    Ok(())
+}
+
+/// Balances of all keys.
+#[derive(Debug, Default)]
+struct Balances {
+    /// Only sum up the total, without the amounts by unlock timestamp and output type
+    total_only: bool,
+    /// Total amount of all counted outputs
+    total: u64,
+    /// Timestamp the outputs without a timelock are counted at, their booking time if `None`
+    no_timelock_timestamp: Option<u32>,
+    /// Amounts by unlock timestamp
+    by_unlock_time: BTreeMap<u32, u64>,
+    /// Addresses contributing to each unlock timestamp
+    contributors: BTreeMap<u32, BTreeSet<String>>,
+    /// Amounts by output type
+    by_output_type: BTreeMap<OutputType, u64>,
+    /// Number of outputs holding at most the minimum storage deposit
+    dust_outputs: usize,
+    /// Total amount of the dust outputs
+    dust_amount: u64,
+    /// Number of outputs holding exactly the minimum storage deposit by unlock timestamp
+    deposit_outputs: BTreeMap<u32, usize>,
+}
+
+impl Balances {
+    /// Adds an output of the address, skipping empty outputs and those that are spent, or were not
+    /// unspent at `as_of`.
+    fn add(
+        &mut self,
+        address: &Bech32Address,
+        output_type: OutputType,
+        output: &OutputWithMetadata,
+        storage_deposit_return: StorageDepositReturn,
+        min_deposit: u64,
+        as_of: Option<u32>,
+    ) {
+        let metadata = output.metadata();
+        let unspent = match as_of {
+            Some(as_of) => {
+                metadata.milestone_timestamp_booked() <= as_of
+                    && metadata
+                        .milestone_timestamp_spent()
+                        .is_none_or(|spent| spent > as_of)
             }
+            None => !metadata.is_spent(),
+        };
+        if !unspent {
+            return;
+        }
+
+        let output = output.output();
+        let amount = storage_deposit_return.amount(output);
+        if amount == 0 {
+            return;
+        }
+        self.total += amount;
+        if self.total_only {
+            return;
+        }
 
-            let output = output.output();
-            if output.amount() == 0 {
+        // increment the balance for the timestamp
+        let ts = unlock_timestamp(output, metadata, self.no_timelock_timestamp);
+        *self.by_unlock_time.entry(ts).or_insert(0) += amount;
+        self.contributors
+            .entry(ts)
+            .or_default()
+            .insert(address.to_string());
+        *self.by_output_type.entry(output_type).or_insert(0) += amount;
+        if amount <= min_deposit {
+            self.dust_outputs += 1;
+            self.dust_amount += amount;
+        }
+        if amount == min_deposit {
+            *self.deposit_outputs.entry(ts).or_insert(0) += 1;
+        }
+    }
+
+    /// Adds the balances of other addresses.
+    fn merge(&mut self, other: Balances) {
+        self.total += other.total;
+        for (ts, amount) in other.by_unlock_time {
+            *self.by_unlock_time.entry(ts).or_insert(0) += amount;
+        }
+        for (ts, addresses) in other.contributors {
+            self.contributors.entry(ts).or_default().extend(addresses);
+        }
+        for (output_type, amount) in other.by_output_type {
+            *self.by_output_type.entry(output_type).or_insert(0) += amount;
+        }
+        self.dust_outputs += other.dust_outputs;
+        self.dust_amount += other.dust_amount;
+        for (ts, count) in other.deposit_outputs {
+            *self.deposit_outputs.entry(ts).or_insert(0) += count;
+        }
+    }
+}
+
+/// Number of outputs fetched at once with `--stream`.
+const STREAM_BATCH_SIZE: usize = 1000;
+
+/// Sums up the balances of all keys by unlock timestamp and by output type.
+async fn collect_balances(
+    args: &Args,
+    clients: &[Client],
+    rent_structure: RentStructure,
+    no_timelock_timestamp: Option<u32>,
+) -> Result<Balances> {
+    let cache = args.cache_outputs.map(|secs| {
+        OutputCache::new(
+            Duration::from_secs(secs),
+            args.storage_deposit_return != StorageDepositReturn::Exclude,
+        )
+    });
+
+    let completed = match &args.resume_state {
+        Some(path) => state::read_completed(path)?,
+        None => Default::default(),
+    };
+
+    let mut balances = Balances::default();
+    for (i, key) in args.key.keys.iter().enumerate() {
+        let client = &clients[i % clients.len()];
+        let secret_manager = args.key.key_format.secret_manager(key)?;
+
+        let address = addresses::first_address(client, i, &secret_manager).await?;
+        if !args.only_addresses.is_empty() && !args.only_addresses.contains(&address) {
+            continue;
+        }
+        if let Some(completed) = completed.get(&address) {
+            balances.merge(completed.balances());
+            continue;
+        }
+
+        let mut address_balances = Balances {
+            total_only: args.total_only,
+            no_timelock_timestamp,
+            ..Default::default()
+        };
+
+        // outputs holding no more than the minimum storage deposit are considered dust
+        let min_deposit = BasicOutputBuilder::new_with_minimum_storage_deposit(rent_structure)
+            .add_unlock_condition(AddressUnlockCondition::new(address))
+            .finish()?
+            .amount();
+
+        for &output_type in &args.output_types {
+            if args.stream {
+                let output_ids = fetch_output_ids(client, address, output_type, args).await?;
+                for batch in output_ids.chunks(STREAM_BATCH_SIZE) {
+                    let mut outputs = client.get_outputs(batch).await?;
+                    sort_by_output_id(&mut outputs);
+                    for output in outputs {
+                        if args.output_metadata {
+                            print_output_metadata(output.metadata())?;
+                        }
+                        if args.strict_balance {
+                            ensure_modeled(&output, args.storage_deposit_return)?;
+                        }
+                        address_balances.add(
+                            &address,
+                            output_type,
+                            &output,
+                            args.storage_deposit_return,
+                            min_deposit,
+                            args.as_of,
+                        );
+                    }
+                }
                 continue;
             }
 
-            // get timestamp of potential timelock
-            let timelock = output
-                .unlock_conditions()
-                .and_then(|uc| uc.timelock().map(|tl| tl.timestamp()));
-            // if there is no timelock, use the booking timestamp
-            let ts = match timelock {
-                Some(ts) => ts,
-                None => metadata.milestone_timestamp_booked(),
+            let cached = cache
+                .as_ref()
+                .and_then(|cache| cache.load(&address, output_type));
+            let outputs_responses = match cached {
+                Some(outputs) => outputs,
+                None => {
+                    let outputs = fetch_outputs(client, address, output_type, args).await?;
+                    if let Some(cache) = &cache {
+                        if let Err(err) = cache.store(&address, output_type, &outputs) {
+                            eprintln!("Warning: failed to cache the outputs: {err:#}");
+                        }
+                    }
+                    outputs
+                }
             };
 
-            // increment the balance for the timestamp
-            *balances.entry(ts).or_insert(0) += output.amount();
+            for output in &outputs_responses {
+                if args.output_metadata {
+                    print_output_metadata(output.metadata())?;
+                }
+                if args.strict_balance {
+                    ensure_modeled(output, args.storage_deposit_return)?;
+                }
+                address_balances.add(
+                    &address,
+                    output_type,
+                    output,
+                    args.storage_deposit_return,
+                    min_deposit,
+                    args.as_of,
+                );
+            }
+        }
+
+        if let Some(path) = &args.resume_state {
+            state::append_completed(path, &state::Completed::new(address, &address_balances))?;
         }
+        balances.merge(address_balances);
     }
 
-    // get the price of IOTA
-    let price = get_price(&args.currency).await?;
-    // print the balances
-    print_balances(balances, price, &args.currency)?;
+    Ok(balances)
+}
 
-    Ok(())
+/// Fetches the outputs of the given type of the address, at most `--max-outputs` of them.
+async fn fetch_outputs(
+    client: &Client,
+    address: Bech32Address,
+    output_type: OutputType,
+    args: &Args,
+) -> Result<Vec<OutputWithMetadata>> {
+    let output_ids = fetch_output_ids(client, address, output_type, args).await?;
+
+    let mut outputs = client.get_outputs(&output_ids).await?;
+    sort_by_output_id(&mut outputs);
+    Ok(outputs)
+}
+
+/// Fetches the ids of the outputs of the given type of the address, at most `--max-outputs` of them.
+async fn fetch_output_ids(
+    client: &Client,
+    address: Bech32Address,
+    output_type: OutputType,
+    args: &Args,
+) -> Result<Vec<OutputId>> {
+    let mut output_ids = output_ids(client, address, output_type, args.condition_filters()).await?;
+    // sorted so that the same outputs are kept with `--max-outputs`
+    output_ids.sort();
+    if let Some(max_outputs) = args.max_outputs.filter(|&max| output_ids.len() > max) {
+        eprintln!(
+            "Warning: {} has {} {} outputs, only counting the first {max_outputs}",
+            address,
+            output_ids.len(),
+            output_type
+        );
+        output_ids.truncate(max_outputs);
+    }
+
+    Ok(output_ids)
+}
+
+/// Returns the ids of the outputs of a type that are counted for an address.
+async fn output_ids(
+    client: &Client,
+    address: Bech32Address,
+    output_type: OutputType,
+    condition_filters: Vec<QueryParameter>,
+) -> Result<Vec<OutputId>> {
+    // Outputs that can be controlled by this address, by default without further unlock constraints
+    let mut owned = vec![QueryParameter::Address(address)];
+    owned.extend(condition_filters);
+    let controlled_aliases = [QueryParameter::StateController(address)];
+
+    let output_ids = match output_type {
+        OutputType::Basic => client.basic_output_ids(owned).await?.items,
+        OutputType::Nft => client.nft_output_ids(owned).await?.items,
+        OutputType::Alias => client.alias_output_ids(controlled_aliases).await?.items,
+        OutputType::Foundry => {
+            let alias_output_ids = client.alias_output_ids(controlled_aliases).await?.items;
+
+            let mut output_ids = Vec::new();
+            for output in client.get_outputs(&alias_output_ids).await? {
+                if let Output::Alias(alias_output) = output.output() {
+                    let alias_id = alias_output.alias_id_non_null(output.metadata().output_id());
+                    let alias_address =
+                        Bech32Address::new(*address.hrp(), AliasAddress::new(alias_id));
+                    output_ids.extend(
+                        client
+                            .foundry_output_ids([QueryParameter::AliasAddress(alias_address)])
+                            .await?
+                            .items,
+                    );
+                }
+            }
+            output_ids
+        }
+    };
+
+    Ok(output_ids)
 }
 
-const PRICE_API_URL: &str = "https://api.coingecko.com/api/v3/simple/price";
+/// Prints the number of outputs of each type per address, which only requires the output ids.
+async fn print_output_counts(args: &Args, clients: &[Client]) -> Result<()> {
+    for (i, key) in args.key.keys.iter().enumerate() {
+        let client = &clients[i % clients.len()];
+        let secret_manager = args.key.key_format.secret_manager(key)?;
 
-async fn get_price(vs_currency: &str) -> Result<f64> {
-    #[derive(Debug, Deserialize)]
-    struct ApiResponse {
-        iota: BTreeMap<String, f64>,
+        let address = addresses::first_address(client, i, &secret_manager).await?;
+        if !args.only_addresses.is_empty() && !args.only_addresses.contains(&address) {
+            continue;
+        }
+
+        let mut counts = Vec::new();
+        for &output_type in &args.output_types {
+            let output_ids =
+                output_ids(client, address, output_type, args.condition_filters()).await?;
+            counts.push(format!("{} {output_type}", output_ids.len()));
+        }
+        println!("{address}: {} outputs", counts.join(", "));
     }
 
-    let client = reqwest::Client::new();
-    let resp: ApiResponse = client
-        .get(PRICE_API_URL)
-        .query(&[
-            ("ids", "iota"),
-            ("vs_currencies", vs_currency),
-            ("precision", "18"),
-        ])
-        .send()
+    Ok(())
+}
+
+/// Returns the timestamp of the bucket an output is counted in.
+fn unlock_timestamp(
+    output: &Output,
+    metadata: &OutputMetadata,
+    no_timelock_timestamp: Option<u32>,
+) -> u32 {
+    // get timestamp of potential timelock
+    let timelock = output
+        .unlock_conditions()
+        .and_then(|uc| uc.timelock().map(|tl| tl.timestamp()));
+    // if there is no timelock, use the configured bucket or the booking timestamp
+    match timelock.or(no_timelock_timestamp) {
+        Some(ts) => ts,
+        None => metadata.milestone_timestamp_booked(),
+    }
+}
+
+/// Prints the details of a single output and the bucket it would be counted in.
+async fn print_output(
+    client: &Client,
+    output_id: OutputId,
+    no_timelock_timestamp: Option<u32>,
+    format: AmountFormat,
+) -> Result<()> {
+    let output = client
+        .get_outputs(&[output_id])
         .await?
-        .json()
-        .await?;
-    let price = *resp
-        .iota
-        .get(vs_currency)
-        .with_context(|| format!("price in '{}' not found", vs_currency))?;
+        .pop()
+        .with_context(|| format!("output {output_id} not found"))?;
+    let metadata = output.metadata();
+    let output = output.output();
+
+    let ts = unlock_timestamp(output, metadata, no_timelock_timestamp);
+    let unlock_time =
+        NaiveDateTime::from_timestamp_opt(ts.into(), 0).context("invalid timestamp")?;
+
+    println!("Output: {output_id}");
+    println!("Kind: {}", output.kind_str());
+    println!("Amount: {}", format.format(output.amount()));
+    println!("Spent: {}", metadata.is_spent());
+    println!("Unlock conditions:");
+    for unlock_condition in output
+        .unlock_conditions()
+        .into_iter()
+        .flat_map(|uc| uc.iter())
+    {
+        println!("  {unlock_condition:?}");
+    }
+    println!("Bucket: {unlock_time}");
+
+    Ok(())
+}
+
+/// Fails if the unspent output has an unlock condition that makes its counted amount ambiguous.
+///
+/// An expiration hands the output to the return address at an unknown point relative to the
+/// report, and a storage deposit return is only modeled when requested with
+/// `--storage-deposit-return`.
+fn ensure_modeled(
+    output: &OutputWithMetadata,
+    storage_deposit_return: StorageDepositReturn,
+) -> Result<()> {
+    let Some(unlock_conditions) = output.output().unlock_conditions() else {
+        return Ok(());
+    };
+    if output.metadata().is_spent() {
+        return Ok(());
+    }
 
-    Ok(price)
+    let condition = if unlock_conditions.expiration().is_some() {
+        "expiration"
+    } else if unlock_conditions.storage_deposit_return().is_some()
+        && storage_deposit_return == StorageDepositReturn::Exclude
+    {
+        "storage deposit return"
+    } else {
+        return Ok(());
+    };
+    bail!(
+        "output {} has an unmodeled {condition} unlock condition",
+        output.metadata().output_id()
+    )
+}
+
+/// Prints when the output was booked and whether it is spent, along with the ledger index of the
+/// node's answer.
+fn print_output_metadata(metadata: &OutputMetadata) -> Result<()> {
+    let booked_time =
+        NaiveDateTime::from_timestamp_opt(metadata.milestone_timestamp_booked().into(), 0)
+            .context("invalid timestamp")?;
+    let status = match metadata.milestone_index_spent() {
+        Some(index) => format!("spent in milestone {index}"),
+        None if metadata.is_spent() => "spent".to_string(),
+        None => "unspent".to_string(),
+    };
+    eprintln!(
+        "{}: booked in milestone {} at {booked_time}, {status}, ledger index {}",
+        metadata.output_id(),
+        metadata.milestone_index_booked(),
+        metadata.ledger_index()
+    );
+
+    Ok(())
+}
+
+/// Prints the total amount of all keys and its value, as JSON if requested.
+fn print_total(total: u64, price: f64, args: &Args) -> Result<()> {
+    let value = total as f64 / 1_000_000. * price;
+    if args.json {
+        let total = serde_json::json!({
+            "currency": args.currency,
+            "price": price,
+            "total_amount": total,
+            "total_value": value,
+        });
+        let json = if args.json_pretty {
+            serde_json::to_string_pretty(&total)?
+        } else {
+            serde_json::to_string(&total)?
+        };
+        println!("{json}");
+    } else {
+        println!(
+            "Total: {} ({value:.2} {})",
+            args.amount_format().format(total),
+            args.currency_label()
+        );
+    }
+
+    Ok(())
 }
 
-fn print_balances(balances: BTreeMap<u32, u64>, price: f64, currency: &str) -> Result<()> {
+/// Unlocks within this many seconds from now are considered imminent.
+const IMMINENT_PERIOD: u32 = 7 * 24 * 60 * 60;
+/// Unlocks of at least this share of the total are considered large.
+const LARGE_SHARE: f64 = 0.1;
+/// Buckets with more contributors only show their count, unless verbose.
+const MAX_LISTED_CONTRIBUTORS: usize = 3;
+
+fn print_balances(
+    balances: BTreeMap<u32, u64>,
+    contributors: &BTreeMap<u32, BTreeSet<String>>,
+    deposit_outputs: &BTreeMap<u32, usize>,
+    price: f64,
+    now: u32,
+    dust_threshold: Option<u64>,
+    args: &Args,
+) -> Result<()> {
     #[derive(Tabled)]
     struct Row {
-        unlock_time: NaiveDateTime,
+        unlock_time: String,
+        timestamp: String,
         amount: String,
+        percent: String,
         value: String,
         cumulative_amount: String,
         cumulative_value: String,
+        spendable: &'static str,
+        deposit_outputs: usize,
+        contributors: String,
     }
 
-    let currency = currency.to_uppercase();
+    let currency = args.currency_label();
+    let format = args.amount_format();
+    let total: u64 = balances.values().sum();
+    let contributors_label = |timestamps: &mut dyn Iterator<Item = &u32>| {
+        let addresses: BTreeSet<_> = timestamps
+            .filter_map(|ts| contributors.get(ts))
+            .flatten()
+            .collect();
+        if args.verbose || addresses.len() <= MAX_LISTED_CONTRIBUTORS {
+            addresses
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        } else {
+            format!("{} addresses", addresses.len())
+        }
+    };
+    let price_at = |ts: u32| match args.assume_price {
+        Some(assumed_price) if ts > now => (assumed_price, " *"),
+        _ => (price, ""),
+    };
 
     let mut amounts = Vec::new();
+    let mut colors = Vec::new();
+    let mut projected = false;
     let mut cumulative = 0;
+    let mut cumulative_value = 0.;
+
+    // collapse the small buckets into a single row, placed where the last of them unlocks so that
+    // the cumulative columns count each of them at its own time
+    let (dust, balances): (BTreeMap<_, _>, BTreeMap<_, _>) = balances
+        .into_iter()
+        .partition(|&(_, amount)| dust_threshold.is_some_and(|threshold| amount < threshold));
+    let dust_row = |cumulative: u64, cumulative_value: f64| {
+        let threshold = dust_threshold?;
+        let amount: u64 = dust.values().sum();
+        let mut value = 0.;
+        let mut marker = "";
+        for (&ts, &bucket_amount) in &dust {
+            let (price, bucket_marker) = price_at(ts);
+            value += bucket_amount as f64 / 1_000_000. * price;
+            if !bucket_marker.is_empty() {
+                marker = bucket_marker;
+            }
+        }
+
+        Some(Row {
+            unlock_time: format!("dust (< {})", format.format(threshold)),
+            timestamp: String::new(),
+            amount: format.format(amount),
+            percent: format!("{:.1}%", amount as f64 / total as f64 * 100.),
+            value: format!("{:.2} {}{}", value, currency, marker),
+            cumulative_amount: format.format(cumulative),
+            cumulative_value: format!("{:.2} {}{}", cumulative_value, currency, marker),
+            spendable: match (dust.keys().next(), dust.keys().next_back()) {
+                (_, Some(&last)) if last <= now => "yes",
+                (Some(&first), _) if first <= now => "partly",
+                _ => "no",
+            },
+            deposit_outputs: dust.keys().filter_map(|ts| deposit_outputs.get(ts)).sum(),
+            contributors: contributors_label(&mut dust.keys()),
+        })
+    };
+
+    let mut pending_dust = dust.iter().peekable();
+    let mut dust_listed = dust.is_empty();
     for (ts, amount) in balances {
+        while let Some((&dust_ts, &dust_amount)) =
+            pending_dust.next_if(|(&dust_ts, _)| dust_ts < ts)
+        {
+            let (price, marker) = price_at(dust_ts);
+            projected |= !marker.is_empty();
+            cumulative += dust_amount;
+            cumulative_value += dust_amount as f64 / 1_000_000. * price;
+        }
+        if !dust_listed && pending_dust.peek().is_none() {
+            amounts.extend(dust_row(cumulative, cumulative_value));
+            dust_listed = true;
+        }
+
         cumulative += amount;
+        let (price, marker) = price_at(ts);
+        projected |= !marker.is_empty();
+        let value = amount as f64 / 1_000_000. * price;
+        cumulative_value += value;
+
+        if ts <= now {
+            colors.push((amounts.len(), Color::FG_GREEN));
+        } else if ts <= now.saturating_add(IMMINENT_PERIOD)
+            && amount as f64 >= total as f64 * LARGE_SHARE
+        {
+            colors.push((amounts.len(), Color::FG_RED));
+        }
         let unlock_time =
             NaiveDateTime::from_timestamp_opt(ts.into(), 0).context("invalid timestamp")?;
 
         amounts.push(Row {
-            unlock_time,
-            amount: format!("{:.6} IOTA", amount as f64 / 1_000_000.),
-            value: format!("{:.2} {}", amount as f64 / 1_000_000. * price, currency),
-            cumulative_amount: format!("{:.6} IOTA", cumulative as f64 / 1_000_000.),
-            cumulative_value: format!("{:.2} {}", cumulative as f64 / 1_000_000. * price, currency),
+            unlock_time: unlock_time.to_string(),
+            timestamp: ts.to_string(),
+            amount: format.format(amount),
+            percent: format!("{:.1}%", amount as f64 / total as f64 * 100.),
+            value: format!("{:.2} {}{}", value, currency, marker),
+            cumulative_amount: format.format(cumulative),
+            cumulative_value: format!("{:.2} {}{}", cumulative_value, currency, marker),
+            spendable: if ts <= now { "yes" } else { "no" },
+            deposit_outputs: deposit_outputs.get(&ts).copied().unwrap_or_default(),
+            contributors: contributors_label(&mut std::iter::once(&ts)),
         });
     }
+    if !dust_listed {
+        for (&dust_ts, &dust_amount) in pending_dust {
+            let (price, marker) = price_at(dust_ts);
+            projected |= !marker.is_empty();
+            cumulative += dust_amount;
+            cumulative_value += dust_amount as f64 / 1_000_000. * price;
+        }
+        amounts.extend(dust_row(cumulative, cumulative_value));
+    }
 
     let mut table = Table::new(amounts);
     table.with(Style::sharp()).with(Alignment::right());
+    if args.color.enabled() {
+        for (i, color) in colors {
+            // the first row is the header
+            table.with(Modify::new(Rows::single(i + 1)).with(color));
+        }
+    }
+    if !args.show_raw_timestamps {
+        table.with(Disable::column(ByColumnName::new("timestamp")));
+    }
+    if !args.show_percent {
+        table.with(Disable::column(ByColumnName::new("percent")));
+    }
+    if !args.show_deposit_outputs {
+        table.with(Disable::column(ByColumnName::new("deposit_outputs")));
+    }
+    if !args.spendable_now {
+        table.with(Disable::column(ByColumnName::new("spendable")));
+    }
+    if !args.show_contributors {
+        table.with(Disable::column(ByColumnName::new("contributors")));
+    }
+    if args.no_cumulative {
+        table
+            .with(Disable::column(ByColumnName::new("cumulative_amount")))
+            .with(Disable::column(ByColumnName::new("cumulative_value")));
+    }
+
+    println!("{table}");
+    if let Some(assumed_price) = args.assume_price.filter(|_| projected) {
+        println!("* projected at an assumed price of {assumed_price} {currency}");
+    }
+
+    Ok(())
+}
+
+fn print_monthly(balances: BTreeMap<u32, u64>, price: f64, args: &Args) -> Result<()> {
+    #[derive(Tabled)]
+    struct Row {
+        month: String,
+        opening_amount: String,
+        unlocked_amount: String,
+        unlocked_value: String,
+        closing_amount: String,
+        closing_value: String,
+    }
+
+    let currency = args.currency_label();
+    let format = args.amount_format();
+
+    let mut months: BTreeMap<(i32, u32), u64> = BTreeMap::new();
+    for (ts, amount) in balances {
+        let unlock_time =
+            NaiveDateTime::from_timestamp_opt(ts.into(), 0).context("invalid timestamp")?;
+        *months
+            .entry((unlock_time.year(), unlock_time.month()))
+            .or_default() += amount;
+    }
+
+    let mut rows = Vec::new();
+    let mut cumulative = 0;
+    for ((year, month), amount) in months {
+        let opening = cumulative;
+        cumulative += amount;
+
+        rows.push(Row {
+            month: format!("{year}-{month:02}"),
+            opening_amount: format.format(opening),
+            unlocked_amount: format.format(amount),
+            unlocked_value: format!("{:.2} {}", amount as f64 / 1_000_000. * price, currency),
+            closing_amount: format.format(cumulative),
+            closing_value: format!("{:.2} {}", cumulative as f64 / 1_000_000. * price, currency),
+        });
+    }
+
+    let mut table = Table::new(rows);
+    table.with(Style::sharp()).with(Alignment::right());
 
     println!("{table}");
 
     Ok(())
 }
+
+/// Balances of all keys on one of the networks of `--network-node`.
+struct NetworkBalances {
+    network: Network,
+    balances: Balances,
+    price: f64,
+    /// The node's notion of now, or the local time
+    now: u32,
+}
+
+/// Sums up the balances of all keys on each network of `--network-node`, in the order the
+/// networks are first given.
+async fn collect_network_balances(args: &Args) -> Result<Vec<NetworkBalances>> {
+    let mut networks = Vec::new();
+    for node in &args.network_node {
+        if !networks.contains(&node.network) {
+            networks.push(node.network);
+        }
+    }
+
+    let mut network_balances = Vec::new();
+    for network in networks {
+        let urls: Vec<_> = args
+            .network_node
+            .iter()
+            .filter(|node| node.network == network)
+            .map(|node| node.url.clone())
+            .collect();
+        let clients = args.node.build_clients_for(&urls).await?;
+        let client = &clients[0];
+        let now = if args.use_local_time {
+            chrono::Utc::now().timestamp().try_into()?
+        } else {
+            client.get_time_checked().await?
+        };
+        if let Some(as_of) = args.as_of {
+            ensure!(as_of <= now, "--as-of must not be in the future");
+        }
+
+        let protocol_parameters = client.get_protocol_parameters().await?;
+        let price = async {
+            get_price(
+                &args.price_source,
+                network.coin_id(),
+                None,
+                &args.currency,
+                args.price_precision,
+                args.retry_price_on_rate_limit.map(Duration::from_secs),
+            )
+            .await
+            .map(|(price, _)| price)
+        };
+        let (balances, price) = tokio::try_join!(
+            collect_balances(
+                args,
+                &clients,
+                *protocol_parameters.rent_structure(),
+                args.no_timelock_bucket.timestamp(args.as_of.unwrap_or(now))
+            ),
+            price
+        )?;
+        network_balances.push(NetworkBalances {
+            network,
+            balances,
+            price,
+            now,
+        });
+    }
+
+    Ok(network_balances)
+}
+
+/// Prints the balances of all networks in one table with a network column, followed by the total
+/// of each network in its own token.
+fn print_network_balances(networks: Vec<NetworkBalances>, args: &Args) -> Result<()> {
+    #[derive(Tabled)]
+    struct Row {
+        network: String,
+        unlock_time: String,
+        timestamp: String,
+        amount: String,
+        value: String,
+        cumulative_amount: String,
+        cumulative_value: String,
+        spendable: &'static str,
+    }
+
+    let currency = args.currency_label();
+    if !args.total_only {
+        let mut amounts = Vec::new();
+        let mut colors = Vec::new();
+        for NetworkBalances {
+            network,
+            balances,
+            price,
+            now,
+        } in &networks
+        {
+            let format = args.network_amount_format(*network);
+            // cumulated per network, as the amounts of different tokens cannot be added up
+            let mut cumulative = 0;
+            let mut cumulative_value = 0.;
+            for (&ts, &amount) in &balances.by_unlock_time {
+                cumulative += amount;
+                let value = amount as f64 / 1_000_000. * price;
+                cumulative_value += value;
+
+                if ts <= *now {
+                    colors.push((amounts.len(), Color::FG_GREEN));
+                }
+                let unlock_time =
+                    NaiveDateTime::from_timestamp_opt(ts.into(), 0).context("invalid timestamp")?;
+
+                amounts.push(Row {
+                    network: network.to_string(),
+                    unlock_time: unlock_time.to_string(),
+                    timestamp: ts.to_string(),
+                    amount: format.format(amount),
+                    value: format!("{value:.2} {currency}"),
+                    cumulative_amount: format.format(cumulative),
+                    cumulative_value: format!("{cumulative_value:.2} {currency}"),
+                    spendable: if ts <= *now { "yes" } else { "no" },
+                });
+            }
+        }
+
+        let mut table = Table::new(amounts);
+        table.with(Style::sharp()).with(Alignment::right());
+        if args.color.enabled() {
+            for (i, color) in colors {
+                // the first row is the header
+                table.with(Modify::new(Rows::single(i + 1)).with(color));
+            }
+        }
+        if !args.show_raw_timestamps {
+            table.with(Disable::column(ByColumnName::new("timestamp")));
+        }
+        if !args.spendable_now {
+            table.with(Disable::column(ByColumnName::new("spendable")));
+        }
+        if args.no_cumulative {
+            table
+                .with(Disable::column(ByColumnName::new("cumulative_amount")))
+                .with(Disable::column(ByColumnName::new("cumulative_value")));
+        }
+        println!("{table}");
+    }
+
+    for NetworkBalances {
+        network,
+        balances,
+        price,
+        ..
+    } in &networks
+    {
+        let value = balances.total as f64 / 1_000_000. * price;
+        println!(
+            "Total on {network}: {} ({value:.2} {currency})",
+            args.network_amount_format(*network).format(balances.total)
+        );
+    }
+
+    Ok(())
+}
+
+fn print_output_type_totals(totals: BTreeMap<OutputType, u64>, price: f64, args: &Args) {
+    #[derive(Tabled)]
+    struct Row {
+        output_type: OutputType,
+        amount: String,
+        value: String,
+    }
+
+    let currency = args.currency_label();
+    let format = args.amount_format();
+    let rows = totals.into_iter().map(|(output_type, amount)| Row {
+        output_type,
+        amount: format.format(amount),
+        value: format!("{:.2} {}", amount as f64 / 1_000_000. * price, currency),
+    });
+
+    let mut table = Table::new(rows);
+    table.with(Style::sharp()).with(Alignment::right());
+
+    println!("{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_node_is_parsed_from_a_network_and_url_pair() {
+        let node = parse_network_node("Shimmer=https://api.shimmer.network").unwrap();
+        assert_eq!(node.network, Network::Shimmer);
+        assert_eq!(node.url, "https://api.shimmer.network");
+
+        assert!(parse_network_node("https://api.shimmer.network").is_err());
+        assert!(parse_network_node("stardust=https://api.shimmer.network").is_err());
+        assert!(parse_network_node("iota=").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! Getting the price of IOTA from an ordered list of sources or a local rates file.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use rand::Rng;
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde::Deserialize;
+
+/// Source of the IOTA price.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PriceSource {
+    /// The CoinGecko API
+    Coingecko,
+    /// The URL given with `--price-url`
+    Custom,
+}
+
+impl std::fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Prices stored in a local file, for use without network access.
+#[derive(Debug, Deserialize)]
+pub struct RatesFile {
+    /// Date the prices were taken at
+    pub date: String,
+    /// Prices of the base tokens by coin id and currency
+    rates: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+impl RatesFile {
+    /// Loads the rates file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read '{}'", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("invalid rates file '{}'", path.display()))
+    }
+
+    /// Returns the price of the coin in the currency, ignoring the case of the currency code.
+    pub fn price(&self, coin_id: &str, vs_currency: &str) -> Result<f64> {
+        self.rates
+            .get(coin_id)
+            .with_context(|| format!("prices of '{coin_id}' not found in the rates file"))?
+            .iter()
+            .find(|(currency, _)| currency.eq_ignore_ascii_case(vs_currency))
+            .map(|(_, &price)| price)
+            .with_context(|| {
+                format!("price of '{coin_id}' in '{vs_currency}' not found in the rates file")
+            })
+    }
+}
+
+/// Returns the price from the first of the sources that succeeds, together with that source.
+pub async fn get_price(
+    sources: &[PriceSource],
+    coin_id: &str,
+    custom_url: Option<&str>,
+    vs_currency: &str,
+    precision: u8,
+    rate_limit_deadline: Option<Duration>,
+) -> Result<(f64, PriceSource)> {
+    for &source in sources {
+        let price = match source {
+            PriceSource::Coingecko => {
+                coingecko_price(coin_id, vs_currency, precision, rate_limit_deadline).await
+            }
+            PriceSource::Custom => {
+                let url = custom_url.context("the custom price source requires a URL")?;
+                custom_price(url, vs_currency).await
+            }
+        };
+        match price {
+            Ok(price) => return Ok((price, source)),
+            Err(err) if sources.len() > 1 => {
+                eprintln!("Warning: price source {source} failed: {err:#}");
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    bail!("none of the price sources returned a price")
+}
+
+const PRICE_API_URL: &str = "https://api.coingecko.com/api/v3/simple/price";
+/// Wait time when a rate limited response does not contain a usable `Retry-After` header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// Fetches the price from the CoinGecko API.
+async fn coingecko_price(
+    coin_id: &str,
+    vs_currency: &str,
+    precision: u8,
+    rate_limit_deadline: Option<Duration>,
+) -> Result<f64> {
+    #[derive(Debug, Deserialize)]
+    #[serde(transparent)]
+    struct ApiResponse {
+        coins: BTreeMap<String, BTreeMap<String, f64>>,
+    }
+
+    let precision = precision.to_string();
+    let deadline = rate_limit_deadline.map(|timeout| Instant::now() + timeout);
+
+    let client = reqwest::Client::new();
+    let resp = loop {
+        let resp = client
+            .get(PRICE_API_URL)
+            .query(&[
+                ("ids", coin_id),
+                ("vs_currencies", vs_currency),
+                ("precision", &precision),
+            ])
+            .send()
+            .await?;
+
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            if let Some(deadline) = deadline {
+                // honor the requested backoff, jittered to not retry in lockstep with other clients
+                let retry_after = resp
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .map_or(DEFAULT_RETRY_AFTER, Duration::from_secs);
+                let wait =
+                    retry_after + Duration::from_millis(rand::thread_rng().gen_range(0..1000));
+
+                if Instant::now() + wait <= deadline {
+                    eprintln!(
+                        "Price API rate limited, retrying in {:.1}s",
+                        wait.as_secs_f64()
+                    );
+                    tokio::time::sleep(wait).await;
+                    continue;
+                }
+            }
+        }
+
+        break resp;
+    };
+    let resp: ApiResponse = resp.error_for_status()?.json().await?;
+    let price = *resp
+        .coins
+        .get(coin_id)
+        .and_then(|prices| prices.get(vs_currency))
+        .with_context(|| format!("price in '{}' not found", vs_currency))?;
+
+    Ok(price)
+}
+
+/// Fetches the price from a URL responding with a plain number, `{currency}` in the URL is
+/// replaced by the currency.
+async fn custom_price(url: &str, vs_currency: &str) -> Result<f64> {
+    let url = url.replace("{currency}", vs_currency);
+    let body = reqwest::get(&url).await?.error_for_status()?.text().await?;
+
+    body.trim()
+        .parse()
+        .with_context(|| format!("invalid price '{}' returned by {url}", body.trim()))
+}
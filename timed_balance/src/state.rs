@@ -0,0 +1,99 @@
+//! Append-only state file recording the balances of the processed addresses, to resume
+//! interrupted runs.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use iota_sdk::types::block::address::Bech32Address;
+use serde::{Deserialize, Serialize};
+
+use crate::{Balances, OutputType};
+
+/// Balances of a single processed address.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Completed {
+    pub address: Bech32Address,
+    pub total: u64,
+    pub by_unlock_time: BTreeMap<u32, u64>,
+    pub by_output_type: BTreeMap<OutputType, u64>,
+    pub dust_outputs: usize,
+    pub dust_amount: u64,
+    #[serde(default)]
+    pub deposit_outputs: BTreeMap<u32, usize>,
+}
+
+impl Completed {
+    pub fn new(address: Bech32Address, balances: &Balances) -> Self {
+        Self {
+            address,
+            total: balances.total,
+            by_unlock_time: balances.by_unlock_time.clone(),
+            by_output_type: balances.by_output_type.clone(),
+            dust_outputs: balances.dust_outputs,
+            dust_amount: balances.dust_amount,
+            deposit_outputs: balances.deposit_outputs.clone(),
+        }
+    }
+
+    /// Returns the balances of the address, with it as the contributor of all its unlocks.
+    pub fn balances(&self) -> Balances {
+        Balances {
+            total: self.total,
+            by_unlock_time: self.by_unlock_time.clone(),
+            contributors: self
+                .by_unlock_time
+                .keys()
+                .map(|&ts| (ts, [self.address.to_string()].into()))
+                .collect(),
+            by_output_type: self.by_output_type.clone(),
+            dust_outputs: self.dust_outputs,
+            dust_amount: self.dust_amount,
+            deposit_outputs: self.deposit_outputs.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns the balances of all addresses recorded in the state file, the last record of an
+/// address replacing earlier ones.
+pub fn read_completed(path: &Path) -> Result<HashMap<Bech32Address, Completed>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read '{}'", path.display())),
+    };
+
+    let mut completed = HashMap::new();
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        // a crash while appending can leave an incomplete last line
+        match serde_json::from_str::<Completed>(line) {
+            Ok(entry) => {
+                completed.insert(entry.address, entry);
+            }
+            Err(e) => eprintln!(
+                "Warning: ignoring invalid line in '{}': {e}",
+                path.display()
+            ),
+        }
+    }
+
+    Ok(completed)
+}
+
+/// Appends the balances of a processed address to the state file.
+pub fn append_completed(path: &Path, completed: &Completed) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open '{}'", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(completed)?)?;
+    file.sync_all()?;
+
+    Ok(())
+}
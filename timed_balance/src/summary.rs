@@ -0,0 +1,160 @@
+//! JSON summary of a report, which can be saved and compared against later runs.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use iota_sdk::{
+    crypto::hashes::{sha::Sha256, Digest},
+    types::block::protocol::ProtocolParameters,
+};
+use serde::{Deserialize, Serialize};
+use tabled::{
+    settings::{Alignment, Style},
+    Table, Tabled,
+};
+
+use common::{format::AmountFormat, network::NetworkInfo};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bucket {
+    pub unlock_time: NaiveDateTime,
+    pub amount: u64,
+    pub value: f64,
+    /// Cumulative totals, unless omitted with `--no-cumulative`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cumulative_amount: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cumulative_value: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Summary {
+    pub network: NetworkInfo,
+    /// Label or URLs of the nodes the report was generated from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    pub currency: String,
+    pub price: f64,
+    pub total_amount: u64,
+    pub total_value: f64,
+    pub balances: Vec<Bucket>,
+    /// Digest of the balances, see [`digest`]
+    #[serde(default)]
+    pub digest: String,
+    /// Total amount per output type, if more than one type was included
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub output_type_totals: BTreeMap<String, u64>,
+}
+
+impl Summary {
+    pub fn new(
+        balances: &BTreeMap<u32, u64>,
+        price: f64,
+        currency: &str,
+        protocol_parameters: &ProtocolParameters,
+        with_cumulative: bool,
+    ) -> Result<Self> {
+        let mut buckets = Vec::new();
+        let mut cumulative = 0;
+        for (&ts, &amount) in balances {
+            cumulative += amount;
+            let unlock_time =
+                NaiveDateTime::from_timestamp_opt(ts.into(), 0).context("invalid timestamp")?;
+
+            buckets.push(Bucket {
+                unlock_time,
+                amount,
+                value: amount as f64 / 1_000_000. * price,
+                cumulative_amount: with_cumulative.then_some(cumulative),
+                cumulative_value: with_cumulative.then_some(cumulative as f64 / 1_000_000. * price),
+            });
+        }
+
+        Ok(Self {
+            network: protocol_parameters.into(),
+            node: None,
+            currency: currency.to_lowercase(),
+            price,
+            total_amount: cumulative,
+            total_value: cumulative as f64 / 1_000_000. * price,
+            balances: buckets,
+            digest: digest(balances),
+            output_type_totals: BTreeMap::new(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read '{}'", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("invalid summary in '{}'", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)
+            .with_context(|| format!("failed to write '{}'", path.display()))
+    }
+}
+
+/// Returns the hex encoded SHA-256 digest of the balances, to cheaply compare reports.
+///
+/// Only the chain state feeds the digest, not the price or display options: for each bucket in
+/// ascending order of the unlock timestamp, the line `<unix timestamp>:<amount in glow>\n`.
+pub fn digest(balances: &BTreeMap<u32, u64>) -> String {
+    let mut hasher = Sha256::new();
+    for (ts, amount) in balances {
+        hasher.update(format!("{ts}:{amount}\n"));
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Prints the buckets that are new, changed or removed compared to the baseline.
+pub fn print_diff(baseline: &Summary, current: &Summary, format: AmountFormat) {
+    #[derive(Tabled)]
+    struct Row {
+        unlock_time: NaiveDateTime,
+        baseline_amount: String,
+        amount: String,
+        change: String,
+        status: &'static str,
+    }
+
+    let mut amounts: BTreeMap<NaiveDateTime, (u64, u64)> = BTreeMap::new();
+    for bucket in &baseline.balances {
+        amounts.entry(bucket.unlock_time).or_default().0 += bucket.amount;
+    }
+    for bucket in &current.balances {
+        amounts.entry(bucket.unlock_time).or_default().1 += bucket.amount;
+    }
+
+    let rows: Vec<_> = amounts
+        .into_iter()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(unlock_time, (before, after))| Row {
+            unlock_time,
+            baseline_amount: format.format(before),
+            amount: format.format(after),
+            change: format.format_change(before, after),
+            status: match (before, after) {
+                (0, _) => "new",
+                (_, 0) => "removed",
+                _ => "changed",
+            },
+        })
+        .collect();
+
+    if rows.is_empty() {
+        println!("No changes compared to the baseline");
+        return;
+    }
+
+    let mut table = Table::new(rows);
+    table.with(Style::sharp()).with(Alignment::right());
+
+    println!("{table}");
+}